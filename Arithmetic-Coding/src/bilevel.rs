@@ -0,0 +1,178 @@
+use std::io::{self, Read, Write};
+use crate::coder::{self, ArithmeticEncoder, ArithmeticDecoder};
+use crate::container;
+
+// Compressed bilevel image layout, all integers little-endian :
+//   magic        4 bytes, "ARBI"
+//   version      u8
+//   width        u32
+//   height       u32
+//   payload      u64 size, then the coded bytes
+//   checksum     u32, CRC-32 of the pixels, one byte per pixel, 1 for black
+pub const MAGIC : [u8; 4] = *b"ARBI";
+pub const VERSION : u8 = 1;
+
+// Largest image accepted when reading, so that a damaged header doesn't make the decoder allocate gigabytes
+pub const MAX_PIXELS : u64 = 1 << 28;
+
+// Probabilities use the whole coder precision, so that predictable pixels cost next to nothing
+const ONE : u32 = coder::MAX_TOTAL;
+
+// Counts of a context are halved past this total, to follow changes in the image
+const COUNT_LIMIT : u32 = 1024;
+
+// Template of already coded pixels forming the context, as (dx, dy) from the current pixel
+// Template 0 of the JBIG2 generic region coder, with its adaptive pixels at their default positions
+static TEMPLATE : [(i32, i32); 16] = [
+    (-1, -2), (0, -2), (1, -2), (-2, -2), (2, -2),
+    (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1), (3, -1), (-3, -1),
+    (-4, 0), (-3, 0), (-2, 0), (-1, 0)
+];
+
+// Adaptive estimate of the probability of a black pixel in one context
+#[derive(Clone, Copy)]
+struct BitModel {
+    zeros : u32,
+    ones : u32
+}
+
+impl BitModel {
+    // Krichevsky-Trofimov estimate, (ones + 1/2) / (total + 1), kept away from 0 and 1
+    fn probability(&self) -> u32 {
+        let p = ((2*self.ones as u64 + 1) * ONE as u64 / (2*(self.zeros + self.ones) as u64 + 2)) as u32;
+        p.clamp(1, ONE - 1)
+    }
+
+    fn update(&mut self, bit : u8) {
+        if bit == 1 { self.ones += 1; } else { self.zeros += 1; }
+        if self.zeros + self.ones > COUNT_LIMIT {
+            self.zeros /= 2;
+            self.ones /= 2;
+        }
+    }
+}
+
+// Context of pixel (x, y) from its coded neighbours, pixels outside of the image count as white
+fn context(pixels : &[u8], width : usize, x : usize, y : usize) -> usize {
+    let mut context = 0;
+    for &(dx, dy) in TEMPLATE.iter() {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        let bit = if nx >= 0 && (nx as usize) < width && ny >= 0 { pixels[nx as usize + ny as usize * width] } else { 0 };
+        context = (context << 1) | bit as usize;
+    }
+    context
+}
+
+// Code the pixels of a bilevel image, one byte per pixel with 1 for black, in raster order
+// Every pixel is coded as a binary decision with the probability learned in its context
+pub fn encode(pixels : &[u8], width : usize, height : usize) -> Vec<u8> {
+    let mut encoder = ArithmeticEncoder::new(vec![]);
+    let mut models = vec![BitModel { zeros : 0, ones : 0 }; 1 << TEMPLATE.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let model = &mut models[context(pixels, width, x, y)];
+            let p = model.probability();
+            let bit = pixels[x + y*width];
+            // A black pixel takes the low part of the interval
+            if bit == 1 { encoder.encode(0, p, ONE); } else { encoder.encode(p, ONE, ONE); }
+            model.update(bit);
+        }
+    }
+
+    // Writing to memory can't fail
+    encoder.finish().unwrap()
+}
+
+pub fn decode(encoded : &[u8], width : usize, height : usize) -> Vec<u8> {
+    let mut decoder = ArithmeticDecoder::new(encoded);
+    let mut models = vec![BitModel { zeros : 0, ones : 0 }; 1 << TEMPLATE.len()];
+    let mut pixels : Vec<u8> = vec![0; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let model = &mut models[context(&pixels, width, x, y)];
+            let p = model.probability();
+            let bit = if decoder.target(ONE) < p { 1 } else { 0 };
+            if bit == 1 { decoder.consume(0, p, ONE); } else { decoder.consume(p, ONE, ONE); }
+            model.update(bit);
+            pixels[x + y*width] = bit;
+        }
+    }
+
+    pixels
+}
+
+pub struct Bilevel {
+    pub width : u32,
+    pub height : u32,
+    pub payload : Vec<u8>,
+    pub checksum : u32
+}
+
+impl Bilevel {
+    pub fn write<W : Write>(&self, out : &mut W) -> io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&self.width.to_le_bytes())?;
+        out.write_all(&self.height.to_le_bytes())?;
+        out.write_all(&(self.payload.len() as u64).to_le_bytes())?;
+        out.write_all(&self.payload)?;
+        out.write_all(&self.checksum.to_le_bytes())
+    }
+
+    pub fn read<R : Read>(input : &mut R) -> io::Result<Bilevel> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC { return Err(container::invalid("Not an arithmetic coded bilevel image".to_string())); }
+
+        let mut version = [0; 1];
+        input.read_exact(&mut version)?;
+        if version[0] != VERSION { return Err(container::invalid(format!("Unsupported version {}, expected {}", version[0], VERSION))); }
+
+        let width = container::read_u32(input)?;
+        let height = container::read_u32(input)?;
+        let pixels = (width as u64).checked_mul(height as u64).filter(|&pixels| pixels <= MAX_PIXELS);
+        if pixels.is_none() { return Err(container::invalid(format!("Image of {}x{} pixels is too large", width, height))); }
+
+        let payload_length = container::read_u64(input)?;
+        let mut payload : Vec<u8> = vec![];
+        input.take(payload_length).read_to_end(&mut payload)?;
+        if payload.len() as u64 != payload_length { return Err(container::invalid("Truncated payload".to_string())); }
+
+        let checksum = container::read_u32(input)?;
+
+        Ok(Bilevel { width, height, payload, checksum })
+    }
+
+    pub fn verify(&self, pixels : &[u8]) -> io::Result<()> {
+        if container::crc32(pixels) != self.checksum {
+            return Err(container::invalid("Checksum mismatch".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn round_trip(width in 1usize..40, pixels in proptest::collection::vec(0u8..=1, 0..1200)) {
+            let height = pixels.len() / width;
+            let pixels = &pixels[..width * height];
+            let encoded = encode(pixels, width, height);
+            prop_assert_eq!(decode(&encoded, width, height), pixels);
+        }
+    }
+
+    #[test]
+    fn oversized_image_is_rejected() {
+        let bilevel = Bilevel { width : 200_000, height : 200_000, payload : vec![], checksum : 0 };
+        let mut header : Vec<u8> = vec![];
+        bilevel.write(&mut header).unwrap();
+        assert_eq!(Bilevel::read(&mut &header[..]).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
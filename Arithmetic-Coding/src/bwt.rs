@@ -0,0 +1,211 @@
+// Burrows-Wheeler transform followed by move-to-front and run-length coding of the zeros, as in bzip2
+// From Burrows & Wheeler, "A block-sorting lossless data compression algorithm"
+// The transform groups bytes by the context that follows them, move-to-front turns that into small values, mostly zeros,
+// which an order-0 model then codes well
+//
+// Output layout, integers little-endian :
+//   block size   u32
+//   then every block of that size, the last one shorter, transformed on its own :
+//   primary      u32, row of the end of block marker in the sorted rotations
+//   symbols      move-to-front values, runs of zeros coded as two zeros then the count of the following ones
+
+// Larger blocks find more context, at the cost of memory and sorting time
+pub const BLOCK_SIZE : usize = 1 << 20;
+
+// Longest run of zeros after the two that start it
+const MAX_RUN : usize = 255;
+
+// Suffix array of data, by prefix doubling from Manber & Myers, "Suffix arrays: a new method for on-line string searches"
+// Suffixes are sorted by their first k bytes, then by their first 2k bytes using the ranks of the first k twice, until all ranks differ
+// The end of data sorts before every byte, so a suffix comes before the longer ones it starts
+fn suffix_array(data : &[u8]) -> Vec<u32> {
+    let n = data.len();
+    let mut suffixes : Vec<u32> = (0..n as u32).collect();
+    // Ranks start at 1, 0 is the end of data
+    let mut rank : Vec<u32> = data.iter().map(|&b| b as u32 + 1).collect();
+    let mut keys : Vec<(u64, u32)> = Vec::with_capacity(n);
+
+    let mut k = 1;
+    loop {
+        let rank_at = |i : usize| if i < n { rank[i] as u64 } else { 0 };
+        keys.clear();
+        keys.extend((0..n).map(|i| ((rank_at(i) << 32) | rank_at(i + k), i as u32)));
+        keys.sort_unstable();
+
+        let mut next = vec![0; n];
+        let mut current = 0;
+        for j in 0..n {
+            if j == 0 || keys[j].0 != keys[j - 1].0 { current += 1; }
+            next[keys[j].1 as usize] = current;
+            suffixes[j] = keys[j].1;
+        }
+        rank = next;
+
+        if current as usize == n || k >= n { break; }
+        k *= 2;
+    }
+    suffixes
+}
+
+// Last column of the sorted rotations of block with an end marker, without the marker, and the row it was on
+fn forward_block(block : &[u8]) -> (Vec<u8>, u32) {
+    // The rotation starting at the marker is the first row, its last byte the last of the block
+    let mut last : Vec<u8> = Vec::with_capacity(block.len());
+    let mut primary = 0;
+    if let Some(&byte) = block.last() { last.push(byte); }
+    for (row, &suffix) in suffix_array(block).iter().enumerate() {
+        if suffix == 0 { primary = row as u32 + 1; } else { last.push(block[suffix as usize - 1]); }
+    }
+    (last, primary)
+}
+
+// Rebuild the block from the last column, by following the mapping from every row to the row of the rotation one byte earlier
+fn inverse_block(last : &[u8], primary : usize) -> Option<Vec<u8>> {
+    let n = last.len();
+    if primary == 0 || primary > n { return None; }
+    // Byte of every row, the marker row excluded
+    let byte_at = |row : usize| if row < primary { last[row] } else { last[row - 1] };
+
+    // First row starting with each byte, after the marker row
+    let mut start = [0usize; 256];
+    let mut counts = [0usize; 256];
+    for &byte in last { counts[byte as usize] += 1; }
+    let mut sum = 1;
+    for byte in 0..256 {
+        start[byte] = sum;
+        sum += counts[byte];
+    }
+
+    let mut previous = vec![0; n + 1];
+    for row in (0..=n).filter(|&row| row != primary) {
+        let byte = byte_at(row) as usize;
+        previous[row] = start[byte];
+        start[byte] += 1;
+    }
+
+    let mut block = vec![0; n];
+    let mut row = 0;
+    for i in (0..n).rev() {
+        block[i] = byte_at(row);
+        row = previous[row];
+    }
+    Some(block)
+}
+
+// Position of every byte in a list where the latest one moves to the front
+fn move_to_front(data : &[u8]) -> Vec<u8> {
+    let mut list : Vec<u8> = (0..=255).collect();
+    data.iter().map(|&byte| {
+        let position = list.iter().position(|&b| b == byte).unwrap();
+        list.copy_within(0..position, 1);
+        list[0] = byte;
+        position as u8
+    }).collect()
+}
+
+fn undo_move_to_front(positions : &[u8]) -> Vec<u8> {
+    let mut list : Vec<u8> = (0..=255).collect();
+    positions.iter().map(|&position| {
+        let byte = list[position as usize];
+        list.copy_within(0..position as usize, 1);
+        list[0] = byte;
+        byte
+    }).collect()
+}
+
+fn encode_runs(symbols : &[u8], out : &mut Vec<u8>) {
+    let mut i = 0;
+    while i < symbols.len() {
+        if symbols[i] != 0 {
+            out.push(symbols[i]);
+            i += 1;
+            continue;
+        }
+        let run = symbols[i..].iter().take(MAX_RUN + 2).take_while(|&&s| s == 0).count();
+        if run == 1 {
+            out.push(0);
+        } else {
+            out.extend_from_slice(&[0, 0, (run - 2) as u8]);
+        }
+        i += run;
+    }
+}
+
+fn read_u32(input : &mut &[u8]) -> Option<u32> {
+    if input.len() < 4 { return None; }
+    let (bytes, rest) = input.split_at(4);
+    *input = rest;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Read back length symbols from input, moving it past them
+fn decode_runs(input : &mut &[u8], length : usize) -> Option<Vec<u8>> {
+    let mut symbols : Vec<u8> = vec![];
+    let mut zeros = 0;
+    while symbols.len() < length {
+        let (&symbol, rest) = input.split_first()?;
+        *input = rest;
+        symbols.push(symbol);
+        if symbol != 0 {
+            zeros = 0;
+        } else if zeros == 1 {
+            let (&count, rest) = input.split_first()?;
+            *input = rest;
+            symbols.resize(symbols.len() + count as usize, 0);
+            zeros = 0;
+        } else {
+            zeros = 1;
+        }
+    }
+    if symbols.len() > length { return None; }
+    Some(symbols)
+}
+
+pub fn forward(data : &[u8], block_size : usize) -> Vec<u8> {
+    assert!(block_size > 0 && block_size <= u32::MAX as usize, "Invalid block size {}", block_size);
+    let mut out : Vec<u8> = vec![];
+    out.extend_from_slice(&(block_size as u32).to_le_bytes());
+    for block in data.chunks(block_size) {
+        let (last, primary) = forward_block(block);
+        out.extend_from_slice(&primary.to_le_bytes());
+        encode_runs(&move_to_front(&last), &mut out);
+    }
+    out
+}
+
+// Undo forward, length is the size of the original data
+// None when the input doesn't hold that many bytes or is damaged
+pub fn inverse(mut input : &[u8], length : u64) -> Option<Vec<u8>> {
+    let block_size = read_u32(&mut input)? as u64;
+    if block_size == 0 { return None; }
+    let mut data : Vec<u8> = vec![];
+    let mut remaining = length;
+    while remaining > 0 {
+        let block_length = remaining.min(block_size) as usize;
+        let primary = read_u32(&mut input)? as usize;
+
+        let last = undo_move_to_front(&decode_runs(&mut input, block_length)?);
+        data.extend(inverse_block(&last, primary)?);
+        remaining -= block_length as u64;
+    }
+    if !input.is_empty() { return None; }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Blocks are transformed separately, the last one shorter
+    #[test]
+    fn several_blocks() {
+        let mut state : u32 = 1;
+        let data : Vec<u8> = (0..5000).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            b"abcd"[(state >> 16) as usize % 4]
+        }).collect();
+        for block_size in [1, 7, 1000, 5000, 1 << 20] {
+            assert_eq!(inverse(&forward(&data, block_size), data.len() as u64), Some(data.clone()));
+        }
+    }
+}
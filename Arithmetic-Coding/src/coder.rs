@@ -0,0 +1,232 @@
+// Integer arithmetic coder, after Witten, Neal & Cleary, "Arithmetic coding for data compression"
+// The interval is kept in 32-bit integers and renormalized bit by bit, so the output streams out as it is coded
+
+use std::io::{self, Read, Write};
+
+// Precision of the coder state, in bits
+const STATE_BITS : u32 = 32;
+const TOP : u64 = (1 << STATE_BITS) - 1;
+const HALF : u64 = 1 << (STATE_BITS - 1);
+const QUARTER : u64 = 1 << (STATE_BITS - 2);
+
+// Largest frequency total a model can use, so that every symbol keeps a non-empty interval
+pub const MAX_TOTAL : u32 = 1 << 16;
+
+// Bytes are handed to the underlying reader and writer in blocks of this size
+const BUFFER_SIZE : usize = 4096;
+
+// Packs bits into bytes, most significant bit first, and writes them out as blocks fill
+// The first write error stops the output, it is reported by take_error or finish
+pub struct BitWriter<W : Write> {
+    output : W,
+    buffer : Vec<u8>,
+    current : u8,
+    filled : u32,
+    error : Option<io::Error>
+}
+
+impl<W : Write> BitWriter<W> {
+    pub fn new(output : W) -> BitWriter<W> {
+        BitWriter { output, buffer : Vec::with_capacity(BUFFER_SIZE), current : 0, filled : 0, error : None }
+    }
+
+    fn flush_buffer(&mut self) {
+        if self.error.is_none() {
+            if let Err(e) = self.output.write_all(&self.buffer) { self.error = Some(e); }
+        }
+        self.buffer.clear();
+    }
+
+    pub fn write_bit(&mut self, bit : u64) {
+        self.current = (self.current << 1) | bit as u8;
+        self.filled += 1;
+        if self.filled == 8 {
+            self.buffer.push(self.current);
+            self.current = 0;
+            self.filled = 0;
+            if self.buffer.len() == BUFFER_SIZE { self.flush_buffer(); }
+        }
+    }
+
+    pub fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    }
+
+    // Flush the last byte, padded with zeros, and give the writer back
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.filled > 0 {
+            self.buffer.push(self.current << (8 - self.filled));
+        }
+        self.flush_buffer();
+        self.take_error()?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+// Reads bits back, most significant bit first. Reads past the end give zeros
+// A read error ends the input the same way, it is reported by take_error
+pub struct BitReader<R : Read> {
+    input : R,
+    buffer : Vec<u8>,
+    // Position in the buffer, in bits
+    position : usize,
+    ended : bool,
+    error : Option<io::Error>
+}
+
+impl<R : Read> BitReader<R> {
+    pub fn new(input : R) -> BitReader<R> {
+        BitReader { input, buffer : vec![], position : 0, ended : false, error : None }
+    }
+
+    fn refill(&mut self) {
+        self.buffer.resize(BUFFER_SIZE, 0);
+        self.position = 0;
+        loop {
+            match self.input.read(&mut self.buffer) {
+                Ok(0) => { self.ended = true; break; },
+                Ok(n) => { self.buffer.truncate(n); return; },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => { self.error = Some(e); self.ended = true; break; }
+            }
+        }
+        self.buffer.clear();
+    }
+
+    pub fn read_bit(&mut self) -> u64 {
+        if self.position == 8*self.buffer.len() {
+            if self.ended { return 0; }
+            self.refill();
+            if self.ended { return 0; }
+        }
+        let bit = (self.buffer[self.position / 8] >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        bit as u64
+    }
+
+    pub fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    }
+}
+
+pub struct ArithmeticEncoder<W : Write> {
+    low : u64,
+    high : u64,
+    // Underflow bits, whose value is only known once the interval leaves the middle half
+    pending : u64,
+    output : BitWriter<W>
+}
+
+impl<W : Write> ArithmeticEncoder<W> {
+    pub fn new(output : W) -> ArithmeticEncoder<W> {
+        ArithmeticEncoder { low : 0, high : TOP, pending : 0, output : BitWriter::new(output) }
+    }
+
+    // First error of the underlying writer, if any, coding carries on without output after it
+    pub fn take_error(&mut self) -> io::Result<()> {
+        self.output.take_error()
+    }
+
+    fn emit(&mut self, bit : u64) {
+        self.output.write_bit(bit);
+        while self.pending > 0 {
+            self.output.write_bit(bit ^ 1);
+            self.pending -= 1;
+        }
+    }
+
+    // Narrow the interval to the symbol occupying [cum_low, cum_high) out of total
+    pub fn encode(&mut self, cum_low : u32, cum_high : u32, total : u32) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + (range * cum_high as u64) / total as u64 - 1;
+        self.low += (range * cum_low as u64) / total as u64;
+
+        // Renormalize
+        loop {
+            if self.high < HALF {
+                self.emit(0);
+            } else if self.low >= HALF {
+                self.emit(1);
+                self.low -= HALF;
+                self.high -= HALF;
+            } else if self.low >= QUARTER && self.high < 3*QUARTER {
+                self.pending += 1;
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+        }
+    }
+
+    // Output enough bits to pin a value inside the final interval, and give the writer back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.pending += 1;
+        if self.low < QUARTER { self.emit(0); } else { self.emit(1); }
+        self.output.finish()
+    }
+}
+
+pub struct ArithmeticDecoder<R : Read> {
+    low : u64,
+    high : u64,
+    value : u64,
+    input : BitReader<R>
+}
+
+impl<R : Read> ArithmeticDecoder<R> {
+    pub fn new(input : R) -> ArithmeticDecoder<R> {
+        let mut input = BitReader::new(input);
+        let mut value = 0;
+        for _ in 0..STATE_BITS {
+            value = (value << 1) | input.read_bit();
+        }
+        ArithmeticDecoder { low : 0, high : TOP, value, input }
+    }
+
+    // First error of the underlying reader, if any, the input reads as zeros after it
+    pub fn take_error(&mut self) -> io::Result<()> {
+        self.input.take_error()
+    }
+
+    // Cumulative frequency pointed at by the coded value, to look the next symbol up in the model
+    pub fn target(&self, total : u32) -> u32 {
+        let range = self.high - self.low + 1;
+        (((self.value - self.low + 1) * total as u64 - 1) / range) as u32
+    }
+
+    // Same narrowing as ArithmeticEncoder::encode, once the symbol is known
+    pub fn consume(&mut self, cum_low : u32, cum_high : u32, total : u32) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + (range * cum_high as u64) / total as u64 - 1;
+        self.low += (range * cum_low as u64) / total as u64;
+
+        loop {
+            if self.high < HALF {
+                // Nothing to remove
+            } else if self.low >= HALF {
+                self.low -= HALF;
+                self.high -= HALF;
+                self.value -= HALF;
+            } else if self.low >= QUARTER && self.high < 3*QUARTER {
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+                self.value -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+            self.value = (self.value << 1) | self.input.read_bit();
+        }
+    }
+}
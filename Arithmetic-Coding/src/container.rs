@@ -0,0 +1,158 @@
+use std::io::{self, Read, Write};
+
+// Compressed file layout, all integers little-endian :
+//   magic        4 bytes, "ARIC"
+//   version      u8
+//   model        u8, how the payload was modeled, see the MODEL_ constants
+//   order        u8, context order of the model, 0 for order-0 models
+//   method       u8, front end transforming the file before the coder, see the METHOD_ constants
+//   length       u64, size of the original file
+//   coded length u64, size of the data handed to the coder, the same as length without a front end or with lz, which codes its own tokens
+//   table        u16 number of entries, then (u16 symbol, u32 count) for each, sorted by symbol
+//                empty for adaptive models
+//   payload      u64 size, then the coded bytes
+//   checksum     u32, CRC-32 of the original file
+pub const MAGIC : [u8; 4] = *b"ARIC";
+pub const VERSION : u8 = 4;
+
+// Order-0 model with the static frequency table stored in the file
+pub const MODEL_STATIC : u8 = 0;
+// Order-0 model learned while coding, see model::AdaptiveModel
+pub const MODEL_ADAPTIVE : u8 = 1;
+// Prediction by partial matching, see ppm::PpmModel
+pub const MODEL_PPM : u8 = 2;
+// Bitwise logistic mixing, see mixing::MixingModel
+pub const MODEL_MIXING : u8 = 3;
+// Static table stored in the file like MODEL_STATIC, coded with rANS instead, see rans::RansTable
+pub const MODEL_RANS : u8 = 4;
+// Canonical Huffman code, its code lengths at the start of the payload, see huffman::HuffmanCode
+pub const MODEL_HUFFMAN : u8 = 5;
+
+// Bytes are coded as they are
+pub const METHOD_NONE : u8 = 0;
+// Burrows-Wheeler transform, move-to-front and run-length coding, see bwt::forward
+pub const METHOD_BWT : u8 = 1;
+// LZ77 matches and literals, coded with their own adaptive models, see lz::encode
+pub const METHOD_LZ : u8 = 2;
+
+// Size of the fields that don't depend on the data, magic, version, model, order, method, lengths, payload size and checksum
+pub const FIXED_BYTES : usize = 4 + 1 + 1 + 1 + 1 + 8 + 8 + 8 + 4;
+
+pub struct Container {
+    pub model : u8,
+    pub order : u8,
+    pub method : u8,
+    pub original_length : u64,
+    pub coded_length : u64,
+    pub frequencies : Vec<(u16, u32)>,
+    pub payload : Vec<u8>,
+    pub checksum : u32
+}
+
+pub fn invalid(message : String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// CRC-32, as in zlib and PNG
+pub fn crc32(data : &[u8]) -> u32 {
+    let mut crc : u32 = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+impl Container {
+    // Size of the serialized frequency table, with its entry count
+    pub fn table_bytes(&self) -> usize {
+        2 + 6*self.frequencies.len()
+    }
+
+    pub fn write<W : Write>(&self, out : &mut W) -> io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&[VERSION, self.model, self.order, self.method])?;
+        out.write_all(&self.original_length.to_le_bytes())?;
+        out.write_all(&self.coded_length.to_le_bytes())?;
+
+        out.write_all(&(self.frequencies.len() as u16).to_le_bytes())?;
+        for &(symbol, count) in self.frequencies.iter() {
+            out.write_all(&symbol.to_le_bytes())?;
+            out.write_all(&count.to_le_bytes())?;
+        }
+
+        out.write_all(&(self.payload.len() as u64).to_le_bytes())?;
+        out.write_all(&self.payload)?;
+        out.write_all(&self.checksum.to_le_bytes())
+    }
+
+    pub fn read<R : Read>(input : &mut R) -> io::Result<Container> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC { return Err(invalid("Not an arithmetic coded file".to_string())); }
+
+        let mut version_model = [0; 4];
+        input.read_exact(&mut version_model)?;
+        let [version, model, order, method] = version_model;
+        if version != VERSION { return Err(invalid(format!("Unsupported version {}, expected {}", version, VERSION))); }
+        if model > MODEL_HUFFMAN { return Err(invalid(format!("Unknown model {}", model))); }
+        if order as usize > crate::ppm::MAX_ORDER.min(crate::mixing::MAX_ORDER) { return Err(invalid(format!("Unsupported order {}", order))); }
+        if method > METHOD_LZ { return Err(invalid(format!("Unknown method {}", method))); }
+
+        let original_length = read_u64(input)?;
+        let coded_length = read_u64(input)?;
+
+        let entries = read_u16(input)?;
+        let mut frequencies : Vec<(u16, u32)> = Vec::with_capacity(entries as usize);
+        for _ in 0..entries {
+            let symbol = read_u16(input)?;
+            let count = read_u32(input)?;
+            frequencies.push((symbol, count));
+        }
+        // Symbols are bytes and the end of file marker 256, sorted
+        let canonical = frequencies.windows(2).all(|pair| pair[0].0 < pair[1].0) && frequencies.iter().all(|&(symbol, count)| symbol <= 256 && count > 0);
+        if !canonical || ((model == MODEL_STATIC || model == MODEL_RANS) && frequencies.is_empty()) {
+            return Err(invalid("Invalid frequency table".to_string()));
+        }
+
+        let payload_length = read_u64(input)?;
+        let mut payload : Vec<u8> = vec![];
+        input.take(payload_length).read_to_end(&mut payload)?;
+        if payload.len() as u64 != payload_length { return Err(invalid("Truncated payload".to_string())); }
+
+        let checksum = read_u32(input)?;
+
+        Ok(Container { model, order, method, original_length, coded_length, frequencies, payload, checksum })
+    }
+
+    // Check decoded data against the stored length and checksum
+    pub fn verify(&self, decoded : &[u8]) -> io::Result<()> {
+        if decoded.len() as u64 != self.original_length {
+            return Err(invalid(format!("Decoded {} bytes, expected {}", decoded.len(), self.original_length)));
+        }
+        if crc32(decoded) != self.checksum {
+            return Err(invalid("Checksum mismatch".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn read_u16<R : Read>(input : &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_u32<R : Read>(input : &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64<R : Read>(input : &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,175 @@
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use crate::coder::{BitWriter, BitReader};
+
+// Code lengths are serialized in 4 bits each, so they are limited to 15
+pub const MAX_CODE_LENGTH : u8 = 15;
+
+// Size of the serialized lengths of the 256 bytes, two per byte
+pub const LENGTHS_BYTES : usize = 128;
+
+// Canonical Huffman code over the bytes, codes follow from the lengths alone
+// Symbols of the same length get consecutive codes in increasing symbol order, shorter codes first
+pub struct HuffmanCode {
+    lengths : Vec<u8>,
+    codes : Vec<u32>,
+    // Symbols sorted by (length, symbol), and for every length the first code and its position in sorted
+    sorted : Vec<u8>,
+    first_code : Vec<u32>,
+    first_index : Vec<usize>,
+    count : Vec<usize>
+}
+
+// Depth of every leaf of a Huffman tree built over counts
+fn huffman_lengths(counts : &[u32]) -> Vec<usize> {
+    let mut heap : BinaryHeap<Reverse<(u64, usize)>> = counts.iter().enumerate().map(|(i, &c)| Reverse((c as u64, i))).collect();
+    // Nodes past the leaves are internal, parent of every node
+    let mut parent : Vec<usize> = vec![0; counts.len()];
+    while heap.len() > 1 {
+        let Reverse((a, i)) = heap.pop().unwrap();
+        let Reverse((b, j)) = heap.pop().unwrap();
+        let node = parent.len();
+        parent.push(0);
+        parent[i] = node;
+        parent[j] = node;
+        heap.push(Reverse((a + b, node)));
+    }
+
+    let root = parent.len() - 1;
+    (0..counts.len()).map(|leaf| {
+        let mut depth = 0;
+        let mut node = leaf;
+        while node != root {
+            node = parent[node];
+            depth += 1;
+        }
+        depth
+    }).collect()
+}
+
+// Move the leaves deeper than max_length up, keeping a complete code
+// From the JPEG standard, annex K.3 : a leaf at the bottom goes up one level with its sibling taking the place of a shallower leaf, which moves down one level
+fn limit_lengths(bits : &mut Vec<usize>, max_length : usize) {
+    for i in (max_length + 1..bits.len()).rev() {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+            while bits[j] == 0 { j -= 1; }
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+    }
+    bits.truncate(max_length + 1);
+}
+
+impl HuffmanCode {
+    // Build a code from the byte counts of find_frequencies, ignoring the end of file marker, with codes of max_length bits at most
+    pub fn new(freqs : &[(u16, u32)], max_length : u8) -> HuffmanCode {
+        let mut symbols : Vec<(u8, u32)> = freqs.iter().filter(|&&(s, _)| s < 256).map(|&(s, c)| (s as u8, c)).collect();
+        let mut lengths = vec![0u8; 256];
+
+        if symbols.len() == 1 {
+            lengths[symbols[0].0 as usize] = 1;
+        } else if symbols.len() > 1 {
+            let counts : Vec<u32> = symbols.iter().map(|&(_, c)| c).collect();
+            let depths = huffman_lengths(&counts);
+
+            // Number of codes of every length, limited, then given back to the symbols from the most frequent one
+            let mut bits = vec![0; depths.iter().max().unwrap() + 1];
+            for &d in depths.iter() { bits[d] += 1; }
+            limit_lengths(&mut bits, max_length as usize);
+
+            symbols.sort_by_key(|&(s, c)| (Reverse(c), s));
+            let mut next = symbols.iter();
+            for (length, &n) in bits.iter().enumerate() {
+                for _ in 0..n {
+                    lengths[next.next().unwrap().0 as usize] = length as u8;
+                }
+            }
+        }
+
+        HuffmanCode::from_lengths(lengths)
+    }
+
+    // Canonical codes for the given length of every byte, 0 for absent ones
+    pub fn from_lengths(lengths : Vec<u8>) -> HuffmanCode {
+        let mut sorted : Vec<u8> = (0..=255).filter(|&s| lengths[s as usize] > 0).collect();
+        sorted.sort_by_key(|&s| (lengths[s as usize], s));
+
+        let mut codes = vec![0; 256];
+        let mut first_code = vec![0; MAX_CODE_LENGTH as usize + 1];
+        let mut first_index = vec![0; MAX_CODE_LENGTH as usize + 1];
+        let mut count = vec![0; MAX_CODE_LENGTH as usize + 1];
+        let mut code : u32 = 0;
+        let mut previous = 0;
+        for (i, &s) in sorted.iter().enumerate() {
+            let length = lengths[s as usize];
+            code <<= length - previous;
+            if count[length as usize] == 0 {
+                first_code[length as usize] = code;
+                first_index[length as usize] = i;
+            }
+            count[length as usize] += 1;
+            codes[s as usize] = code;
+            code += 1;
+            previous = length;
+        }
+
+        HuffmanCode { lengths, codes, sorted, first_code, first_index, count }
+    }
+
+    // Whether the lengths fit a prefix code, sum of 2^-length at most 1
+    pub fn is_valid(lengths : &[u8]) -> bool {
+        lengths.iter().filter(|&&l| l > 0).map(|&l| 1u32 << (MAX_CODE_LENGTH - l)).sum::<u32>() <= 1 << MAX_CODE_LENGTH
+    }
+
+    // Lengths of the 256 bytes, two per byte
+    pub fn write_lengths(&self, out : &mut Vec<u8>) {
+        for pair in self.lengths.chunks(2) {
+            out.push((pair[0] << 4) | pair[1]);
+        }
+    }
+
+    pub fn read_lengths(input : &[u8]) -> Option<HuffmanCode> {
+        if input.len() < LENGTHS_BYTES { return None; }
+        let lengths : Vec<u8> = input[..LENGTHS_BYTES].iter().flat_map(|&b| vec![b >> 4, b & 15]).collect();
+        if !HuffmanCode::is_valid(&lengths) { return None; }
+        Some(HuffmanCode::from_lengths(lengths))
+    }
+
+    pub fn encode(&self, data : &[u8]) -> Vec<u8> {
+        let mut output = BitWriter::new(vec![]);
+        for &byte in data {
+            let length = self.lengths[byte as usize];
+            assert!(length > 0, "Byte {} missing from the code", byte);
+            let code = self.codes[byte as usize];
+            for i in (0..length).rev() {
+                output.write_bit(((code >> i) & 1) as u64);
+            }
+        }
+        // Writing to memory can't fail
+        output.finish().unwrap()
+    }
+
+    // Decode length bytes, bit by bit with the canonical code tables, stopping early on a code that isn't assigned
+    // Zeros past the end of the input always make a code, so length must fit in the input, 8 bytes per input byte at most
+    pub fn decode(&self, encoded : &[u8], length : u64) -> Vec<u8> {
+        let mut input = BitReader::new(encoded);
+        let mut result : Vec<u8> = vec![];
+
+        'symbols: for _ in 0..length {
+            let mut code : u32 = 0;
+            for l in 1..=MAX_CODE_LENGTH as usize {
+                code = (code << 1) | input.read_bit() as u32;
+                if self.count[l] > 0 && code >= self.first_code[l] && ((code - self.first_code[l]) as usize) < self.count[l] {
+                    result.push(self.sorted[self.first_index[l] + (code - self.first_code[l]) as usize]);
+                    continue 'symbols;
+                }
+            }
+            break;
+        }
+
+        result
+    }
+}
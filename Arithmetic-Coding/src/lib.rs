@@ -0,0 +1,30 @@
+// Arithmetic coding with pluggable models, and the other entropy coders it is compared with
+// Encoder and Decoder wrap a writer and a reader, so that any model can compress a stream :
+//   let mut encoder = Encoder::new(output, AdaptiveModel::new(ALPHABET));
+//   encoder.write_all(data)?;
+//   let output = encoder.finish()?;
+
+pub mod bilevel;
+pub mod bwt;
+pub mod coder;
+pub mod container;
+pub mod huffman;
+pub mod lz;
+pub mod mixing;
+pub mod model;
+pub mod ppm;
+pub mod rans;
+pub mod report;
+pub mod stream;
+
+pub use coder::{ArithmeticEncoder, ArithmeticDecoder};
+pub use mixing::MixingModel;
+pub use model::{Model, StaticModel, AdaptiveModel, find_frequencies};
+pub use ppm::PpmModel;
+pub use stream::{Encoder, Decoder};
+
+// Symbols are bytes, plus an end of file marker
+pub type Symbol = u16;
+pub const EOF : Symbol = 256;
+// Number of symbols, for the models that take an alphabet size
+pub const ALPHABET : usize = EOF as usize + 1;
@@ -0,0 +1,221 @@
+use crate::coder::{ArithmeticEncoder, ArithmeticDecoder};
+use crate::model::{Model, AdaptiveModel};
+use crate::{ALPHABET, EOF};
+
+// LZ77 with the literal flag of LZSS, from Ziv & Lempel, "A universal algorithm for sequential data compression"
+// and Storer & Szymanski, "Data compression via textual substitution"
+// The data becomes literals and matches, copies of length bytes from distance bytes back, found through hash chains as in deflate
+// Literals, lengths and distances are coded with separate adaptive models, the literal model also telling matches and the end apart
+
+// Shortest and longest match, lengths are coded as one byte
+pub const MIN_MATCH : usize = 3;
+pub const MAX_MATCH : usize = MIN_MATCH + 255;
+
+// Window size, as a power of two, by default and at most
+pub const WINDOW_BITS : u8 = 16;
+pub const MAX_WINDOW_BITS : u8 = 24;
+
+// Symbol of the literal model announcing a match, after the bytes and EOF
+const MATCH : usize = ALPHABET;
+
+// Distances are coded as a slot, then the extra bits picking the distance in the slot
+// Slots 0 to 3 are distances 1 to 4, then every power of two is split in two slots as in deflate
+const DISTANCE_SLOTS : usize = 2 * MAX_WINDOW_BITS as usize;
+
+// Extra bits are sent in chunks of this many bits at most, to stay within the coder precision
+const CHUNK_BITS : u32 = 16;
+
+// Positions hash on their first MIN_MATCH bytes
+const HASH_BITS : u32 = 16;
+
+// Candidates tried for every position, longer chains find longer matches in more time
+const MAX_CHAIN : usize = 128;
+
+// No position yet in a hash chain
+const NONE : u32 = u32::MAX;
+
+// Latest position of every hash, and for every position in the window the previous one with the same hash
+struct MatchFinder {
+    head : Vec<u32>,
+    previous : Vec<u32>,
+    window : usize
+}
+
+impl MatchFinder {
+    fn new(window_bits : u8) -> MatchFinder {
+        let window = 1 << window_bits;
+        MatchFinder { head : vec![NONE; 1 << HASH_BITS], previous : vec![NONE; window], window }
+    }
+
+    fn hash(data : &[u8], position : usize) -> usize {
+        let bytes = (data[position] as u32) << 16 | (data[position + 1] as u32) << 8 | data[position + 2] as u32;
+        (bytes.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data : &[u8], position : usize) {
+        if position + MIN_MATCH > data.len() { return; }
+        let hash = MatchFinder::hash(data, position);
+        self.previous[position & (self.window - 1)] = self.head[hash];
+        self.head[hash] = position as u32;
+    }
+
+    // Longest match for the data at position among the positions inserted before it, as (length, distance)
+    fn find(&self, data : &[u8], position : usize) -> (usize, usize) {
+        if position + MIN_MATCH > data.len() { return (0, 0); }
+        let longest = (data.len() - position).min(MAX_MATCH);
+        let mut best = (0, 0);
+
+        let mut candidate = self.head[MatchFinder::hash(data, position)];
+        for _ in 0..MAX_CHAIN {
+            // Past the window, or a link overwritten by a newer position
+            if candidate == NONE || position - candidate as usize >= self.window { break; }
+            let start = candidate as usize;
+            let length = data[start..].iter().zip(&data[position..position + longest]).take_while(|(a, b)| a == b).count();
+            if length > best.0 {
+                best = (length, position - start);
+                if length == longest { break; }
+            }
+            let next = self.previous[start & (self.window - 1)];
+            if next == NONE || next >= candidate { break; }
+            candidate = next;
+        }
+
+        if best.0 >= MIN_MATCH { best } else { (0, 0) }
+    }
+}
+
+// Slot of a distance, with the number of extra bits and the first distance of the slot
+fn distance_slot(distance : usize) -> (usize, u32, usize) {
+    let value = distance - 1;
+    if value < 4 { return (value, 0, distance); }
+    let bits = usize::BITS - 1 - value.leading_zeros();
+    let half = (value >> (bits - 1)) & 1;
+    (2*bits as usize + half, bits - 1, ((2 | half) << (bits - 1)) + 1)
+}
+
+fn slot_distance(slot : usize) -> (u32, usize) {
+    if slot < 4 { return (0, slot + 1); }
+    let bits = slot as u32 / 2;
+    let half = slot & 1;
+    (bits - 1, ((2 | half) << (bits - 1)) + 1)
+}
+
+// Models of the three streams, the same on both sides
+struct Models {
+    literals : AdaptiveModel,
+    lengths : AdaptiveModel,
+    distances : AdaptiveModel
+}
+
+impl Models {
+    fn new() -> Models {
+        Models {
+            literals : AdaptiveModel::new(ALPHABET + 1),
+            lengths : AdaptiveModel::new(MAX_MATCH - MIN_MATCH + 1),
+            distances : AdaptiveModel::new(DISTANCE_SLOTS)
+        }
+    }
+}
+
+// Code data as literals and matches within the last 2^window_bits bytes
+// A match found one byte later is tried before taking one, and taken instead when it is longer
+pub fn encode(data : &[u8], window_bits : u8) -> Vec<u8> {
+    assert!(window_bits <= MAX_WINDOW_BITS, "LZ window is at most 2^{} bytes", MAX_WINDOW_BITS);
+    let mut encoder = ArithmeticEncoder::new(vec![]);
+    let mut models = Models::new();
+    let mut finder = MatchFinder::new(window_bits);
+
+    let mut position = 0;
+    let mut current = finder.find(data, 0);
+    while position < data.len() {
+        let (length, distance) = current;
+        finder.insert(data, position);
+        let next = if length > 0 { finder.find(data, position + 1) } else { (0, 0) };
+
+        if length == 0 || next.0 > length {
+            models.literals.encode(&mut encoder, data[position] as usize);
+            position += 1;
+            current = if length == 0 { finder.find(data, position) } else { next };
+            continue;
+        }
+
+        models.literals.encode(&mut encoder, MATCH);
+        models.lengths.encode(&mut encoder, length - MIN_MATCH);
+        let (slot, extra_bits, first) = distance_slot(distance);
+        models.distances.encode(&mut encoder, slot);
+        let mut extra = distance - first;
+        let mut remaining = extra_bits;
+        while remaining > 0 {
+            let bits = remaining.min(CHUNK_BITS);
+            let chunk = (extra & ((1 << bits) - 1)) as u32;
+            encoder.encode(chunk, chunk + 1, 1 << bits);
+            extra >>= bits;
+            remaining -= bits;
+        }
+
+        for p in position + 1..position + length {
+            finder.insert(data, p);
+        }
+        position += length;
+        current = finder.find(data, position);
+    }
+    models.literals.encode(&mut encoder, EOF as usize);
+
+    // Writing to memory can't fail
+    encoder.finish().unwrap()
+}
+
+// Stops at the end of file symbol, after length bytes at most, or at a match reaching before the start of the data
+pub fn decode(encoded : &[u8], length : u64) -> Vec<u8> {
+    let mut decoder = ArithmeticDecoder::new(encoded);
+    let mut models = Models::new();
+    let mut data : Vec<u8> = vec![];
+
+    while (data.len() as u64) < length {
+        let symbol = models.literals.decode(&mut decoder);
+        if symbol == EOF as usize { break; }
+        if symbol != MATCH {
+            data.push(symbol as u8);
+            continue;
+        }
+
+        let match_length = models.lengths.decode(&mut decoder) + MIN_MATCH;
+        let (extra_bits, first) = slot_distance(models.distances.decode(&mut decoder));
+        let mut extra = 0;
+        let mut shift = 0;
+        while shift < extra_bits {
+            let bits = (extra_bits - shift).min(CHUNK_BITS);
+            let chunk = decoder.target(1 << bits);
+            decoder.consume(chunk, chunk + 1, 1 << bits);
+            extra |= (chunk as usize) << shift;
+            shift += bits;
+        }
+        let distance = first + extra;
+        if distance > data.len() { break; }
+
+        // Byte by byte, a match may overlap the bytes it produces
+        let start = data.len() - distance;
+        let count = match_length.min((length - data.len() as u64) as usize);
+        for i in 0..count {
+            data.push(data[start + i]);
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every distance of the largest window lands in a slot that gives it back
+    #[test]
+    fn distance_slots() {
+        for distance in 1..1 << MAX_WINDOW_BITS {
+            let (slot, extra_bits, first) = distance_slot(distance);
+            assert!(slot < DISTANCE_SLOTS);
+            assert_eq!(slot_distance(slot), (extra_bits, first));
+            assert!(distance >= first && distance - first < 1 << extra_bits);
+        }
+    }
+}
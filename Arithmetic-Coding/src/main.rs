@@ -1,133 +1,478 @@
 use structopt::StructOpt;
 use std::fs;
-use std::collections::HashMap;
-use rug::{Float, ops::AssignRound, float::Round};
-use std::mem;
+use std::io::{self, Read, Write};
+use std::time::Instant;
+
+use arithmetic_coding::{Model, StaticModel, AdaptiveModel, PpmModel, MixingModel, Encoder, Decoder, find_frequencies, ALPHABET};
+use arithmetic_coding::{bilevel, bwt, container, huffman, lz, mixing, ppm, report};
+use arithmetic_coding::container::Container;
+use arithmetic_coding::bilevel::Bilevel;
+use arithmetic_coding::rans::RansTable;
+use arithmetic_coding::huffman::HuffmanCode;
+use arithmetic_coding::report::SizeReport;
 
 #[derive(StructOpt, Debug)]
-#[structopt(name = "basic")]
-struct Opt {
-    #[structopt(name = "FILE")]
-    file: String
+#[structopt(name = "arithmetic-coding", about = "Compress files with an arithmetic coder")]
+enum Opt {
+    #[structopt(about = "Compress a file")]
+    Encode {
+        #[structopt(flatten)]
+        files: Files,
+        /// Static table stored in the file, adaptive order-0 model learned while coding,
+        /// prediction by partial matching or bitwise logistic mixing of the contexts up to --order
+        #[structopt(short, long, default_value = "static", possible_values = &["static", "adaptive", "ppm", "mix"])]
+        model: String,
+        /// Context order of the ppm and mix models
+        #[structopt(long, default_value = "4")]
+        order: u8,
+        /// Entropy coder, rans and huffman only work with the static model
+        #[structopt(short, long, default_value = "arithmetic", possible_values = &["arithmetic", "rans", "huffman"])]
+        coder: String,
+        /// Longest Huffman code, in bits
+        #[structopt(long, default_value = "15")]
+        max_code_length: u8,
+        /// Front end run before the coder, bwt for the Burrows-Wheeler transform followed by move-to-front and run-length coding,
+        /// lz for LZ77 matches, coded with their own adaptive models in place of --coder and --model
+        #[structopt(long, default_value = "none", possible_values = &["none", "bwt", "lz"])]
+        method: String,
+        /// Size of the lz window, as a power of two
+        #[structopt(long, default_value = "16")]
+        window_bits: u8
+    },
+    #[structopt(about = "Restore a compressed file")]
+    Decode(Files),
+    #[structopt(about = "Compare the size and speed of every coder and model on a file")]
+    Bench {
+        /// Input file, stdin when missing or "-"
+        #[structopt(name = "INPUT")]
+        input: Option<String>
+    },
+    #[structopt(about = "Compress a bilevel image, such as a halftone, with a context model of its neighbouring pixels")]
+    EncodeImage(Files),
+    #[structopt(about = "Restore a compressed bilevel image as a PNG")]
+    DecodeImage(Files)
 }
 
+#[derive(StructOpt, Debug)]
+struct Files {
+    /// Input file, stdin when missing or "-"
+    #[structopt(name = "INPUT")]
+    input: Option<String>,
+    /// Output file, stdout when missing or "-"
+    #[structopt(short, long)]
+    output: Option<String>,
+    /// Report sizes, compression ratio and entropy on stderr
+    #[structopt(long)]
+    stats: bool
+}
 
-fn find_frequencies(data : &String) -> HashMap<char, u32> {
-    let mut frequencies : HashMap<char, u32> = HashMap::new();
 
-    for c in data.chars() {
-        // Read previous character count
-        let mut cur_count : u32 = 0;
-        if let Some(&count) = frequencies.get(&c) { cur_count = count; }
+// Code contents and the end of file symbol with model, through the streaming encoder
+fn model_encode<M : Model>(contents : &[u8], model : M) -> io::Result<Vec<u8>> {
+    let mut encoder = Encoder::new(vec![], model);
+    encoder.write_all(contents)?;
+    encoder.finish()
+}
 
-        // Increase it
-        let entry = frequencies.entry(c).or_insert(cur_count);
-        *entry = *entry + 1;
+// Stops at the end of file symbol, or after length symbols at most
+// The bit reader feeds zeros past the end of the payload, so a damaged payload could otherwise keep the decoder going forever
+fn model_decode<M : Model>(encoded : &[u8], model : M, length : u64) -> io::Result<Vec<u8>> {
+    let mut result : Vec<u8> = vec![];
+    Decoder::new(encoded, model).take(length).read_to_end(&mut result)?;
+    Ok(result)
+}
+
+fn read_input(path : &Option<String>) -> io::Result<Vec<u8>> {
+    match path.as_deref() {
+        None | Some("-") => {
+            let mut data : Vec<u8> = vec![];
+            io::stdin().lock().read_to_end(&mut data)?;
+            Ok(data)
+        },
+        Some(path) => fs::read(path)
     }
+}
 
-    return frequencies;
+fn write_output(path : &Option<String>, data : &[u8]) -> io::Result<()> {
+    match path.as_deref() {
+        None | Some("-") => {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            out.write_all(data)?;
+            out.flush()
+        },
+        Some(path) => fs::write(path, data)
+    }
 }
 
-fn compression_ratio(before: &String, after: &Float) -> f64 {
-    let before_size = mem::size_of_val(&before[..]);
-    let after_size = after.prec() + 32;
-    println!("before : {:?}, after : {:?}", before_size, after_size);
-    return after_size as f64 / before_size as f64;    
+// Compress contents into a container, through the front end of --method then with one of the models offered by --model
+fn compress(contents : &[u8], method : &str, coder : &str, model : &str, order : u8, max_code_length : u8, window_bits : u8) -> io::Result<Container> {
+    if order as usize > ppm::MAX_ORDER.min(mixing::MAX_ORDER) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Order {} is too large", order)));
+    }
+    if coder != "arithmetic" && model != "static" {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The {} coder only works with the static model", coder)));
+    }
+    // Codes of 8 bits are enough for every byte
+    if !(8..=huffman::MAX_CODE_LENGTH).contains(&max_code_length) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The maximum code length must be between 8 and {}", huffman::MAX_CODE_LENGTH)));
+    }
+    if method == "lz" && (coder != "arithmetic" || model != "static") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The lz method codes its tokens with its own models, it takes no --coder or --model".to_string()));
+    }
+    if window_bits > lz::MAX_WINDOW_BITS {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The lz window is at most {} bits", lz::MAX_WINDOW_BITS)));
+    }
 
+    let transformed;
+    let (method, coded) = match method {
+        "bwt" => {
+            transformed = bwt::forward(contents, bwt::BLOCK_SIZE);
+            (container::METHOD_BWT, &transformed[..])
+        },
+        "lz" => (container::METHOD_LZ, contents),
+        _ => (container::METHOD_NONE, contents)
+    };
+
+    let (model, order, frequencies, encoded) = match model {
+        _ if method == container::METHOD_LZ => (container::MODEL_ADAPTIVE, 0, vec![], lz::encode(coded, window_bits)),
+        _ if coder == "rans" => {
+            let frequencies = find_frequencies(coded);
+            let encoded = RansTable::new(&frequencies, ALPHABET).encode(coded);
+            (container::MODEL_RANS, 0, frequencies, encoded)
+        },
+        // Only the code lengths are stored, at the start of the payload
+        _ if coder == "huffman" => {
+            let code = HuffmanCode::new(&find_frequencies(coded), max_code_length);
+            let mut encoded : Vec<u8> = vec![];
+            code.write_lengths(&mut encoded);
+            encoded.extend(code.encode(coded));
+            (container::MODEL_HUFFMAN, 0, vec![], encoded)
+        },
+        "adaptive" => (container::MODEL_ADAPTIVE, 0, vec![], model_encode(coded, AdaptiveModel::new(ALPHABET))?),
+        "ppm" => (container::MODEL_PPM, order, vec![], model_encode(coded, PpmModel::new(ALPHABET, order as usize))?),
+        "mix" => (container::MODEL_MIXING, order, vec![], model_encode(coded, MixingModel::new(order as usize))?),
+        _ => {
+            let frequencies = find_frequencies(coded);
+            let encoded = model_encode(coded, StaticModel::new(&frequencies))?;
+            (container::MODEL_STATIC, 0, frequencies, encoded)
+        }
+    };
+
+    Ok(Container {
+        model,
+        order,
+        method,
+        original_length : contents.len() as u64,
+        coded_length : coded.len() as u64,
+        frequencies,
+        payload : encoded,
+        checksum : container::crc32(contents)
+    })
 }
 
-fn find_bounds(freqs: &HashMap<char, u32>, filesize: u32, step_precision: u32) -> HashMap<char, (Float, Float)> {
-    let mut bounds : HashMap<char, (Float, Float)> = HashMap::new();
+// Decode the payload of a container and undo its front end, checked against its length and checksum
+fn decompress(container : &Container) -> io::Result<Vec<u8>> {
+    let (payload, length) = (&container.payload[..], container.coded_length);
+    let decoded = match container.model {
+        _ if container.method == container::METHOD_LZ => lz::decode(payload, length),
+        container::MODEL_ADAPTIVE => model_decode(payload, AdaptiveModel::new(ALPHABET), length)?,
+        container::MODEL_PPM => model_decode(payload, PpmModel::new(ALPHABET, container.order as usize), length)?,
+        container::MODEL_MIXING => model_decode(payload, MixingModel::new(container.order as usize), length)?,
+        container::MODEL_RANS => RansTable::new(&container.frequencies, ALPHABET).decode(payload, length)
+            .ok_or_else(|| container::invalid("Truncated rANS payload".to_string()))?,
+        container::MODEL_HUFFMAN => {
+            let code = HuffmanCode::read_lengths(payload).ok_or_else(|| container::invalid("Invalid Huffman code lengths".to_string()))?;
+            // Every code is one bit at least, and the reader gives zeros past the end, which always make a code
+            if length > 8 * (payload.len() - huffman::LENGTHS_BYTES) as u64 {
+                return Err(container::invalid(format!("{} bytes can't be coded in {} payload bytes", length, payload.len() - huffman::LENGTHS_BYTES)));
+            }
+            code.decode(&payload[huffman::LENGTHS_BYTES..], length)
+        },
+        _ => model_decode(payload, StaticModel::new(&container.frequencies), length)?
+    };
+    let decoded = match container.method {
+        container::METHOD_BWT => bwt::inverse(&decoded, container.original_length).ok_or_else(|| container::invalid("Invalid Burrows-Wheeler blocks".to_string()))?,
+        _ => decoded
+    };
+    container.verify(&decoded)?;
+    Ok(decoded)
+}
 
-    let mut prevhigh = Float::with_val(step_precision, 0.0);
-    for (&c, &count) in freqs {
-        let low = prevhigh;
-        let high = Float::with_val(step_precision, low.clone() + (count as f64 / filesize as f64));
-        prevhigh = Float::with_val(step_precision, high.clone());
+// Huffman code lengths are stored in the payload, they count as model
+fn size_report(original : &[u8], container : &Container) -> SizeReport {
+    let model_in_payload = if container.model == container::MODEL_HUFFMAN { huffman::LENGTHS_BYTES } else { 0 };
+    SizeReport::new(original, container, model_in_payload)
+}
 
-        bounds.insert(c, (low, high));
+fn encode(files : &Files, method : &str, coder : &str, model : &str, order : u8, max_code_length : u8, window_bits : u8) -> io::Result<()> {
+    let contents = read_input(&files.input)?;
+
+    let container = compress(&contents, method, coder, model, order, max_code_length, window_bits)?;
+    let mut compressed : Vec<u8> = vec![];
+    container.write(&mut compressed)?;
+
+    if files.stats {
+        size_report(&contents, &container).write(&mut io::stderr())?;
     }
 
-    return bounds;
+    write_output(&files.output, &compressed)
 }
 
-fn arithmetic_encode(file: &String, bounds: &HashMap<char, (Float, Float)>, step_precision: u32) -> Float {
+fn decode(files : &Files) -> io::Result<()> {
+    let compressed = read_input(&files.input)?;
+    let container = Container::read(&mut &compressed[..])?;
 
-    // Start with 1-bit precision
-    let mut high : Float = Float::with_val(32, 1);
-    let mut low : Float  = Float::with_val(32, 0);
+    // Decode file
+    let decoded = decompress(&container)?;
 
-    for c in file.chars() {
+    if files.stats {
+        size_report(&decoded, &container).write(&mut io::stderr())?;
+    }
 
-        // Increase the precision of the floats
-        high.set_prec(high.prec() + step_precision);
-        low.set_prec(low.prec() + step_precision);
+    write_output(&files.output, &decoded)
+}
 
-        //println!("{:?} : {:?}, {:?}, {:?}, {:?}, ", c, low.prec(), high.prec(), high, low);
-        
-        let range = high.clone() - low.clone();
+// Front ends, coders and models compared by bench, as (method, coder, model, order)
+static BENCH_METHODS : [(&str, &str, &str, u8); 8] = [
+    ("none", "arithmetic", "static", 0),
+    ("none", "arithmetic", "adaptive", 0),
+    ("none", "arithmetic", "ppm", 4),
+    ("none", "arithmetic", "mix", 4),
+    ("none", "rans", "static", 0),
+    ("none", "huffman", "static", 0),
+    ("bwt", "arithmetic", "adaptive", 0),
+    ("lz", "arithmetic", "static", 0)
+];
+
+fn bench(input : &Option<String>) -> io::Result<()> {
+    let contents = read_input(input)?;
+    let megabytes = contents.len() as f64 / 1e6;
+
+    println!("{:<12} {:>10} {:>8} {:>12} {:>14} {:>14}", "method", "bytes", "ratio", "bits/byte", "encode MB/s", "decode MB/s");
+    for &(method, coder, model, order) in BENCH_METHODS.iter() {
+        let start = Instant::now();
+        let container = compress(&contents, method, coder, model, order, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS)?;
+        let mut compressed : Vec<u8> = vec![];
+        container.write(&mut compressed)?;
+        let encode_time = start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        decompress(&Container::read(&mut &compressed[..])?)?;
+        let decode_time = start.elapsed().as_secs_f64();
+
+        let name = if order > 0 { format!("{}-{}", model, order) } else if coder == "arithmetic" { model.to_string() } else { coder.to_string() };
+        let name = match method {
+            "none" => name,
+            // Codes with its own models
+            "lz" => method.to_string(),
+            _ => format!("{}+{}", method, name)
+        };
+        let report = size_report(&contents, &container);
+        println!("{:<12} {:>10} {:>8} {:>12} {:>14.2} {:>14.2}", name, report.total_bytes, report::format_ratio(report.ratio()),
+            report::format_ratio(report.bits_per_symbol()), megabytes / encode_time, megabytes / decode_time);
+    }
+    Ok(())
+}
 
-        //println!("{:?} : {:?}, {:?}, {:?}, {:?}, {:?}, ", c, low.prec(), high.prec(), range, high, low);
+fn encode_image(files : &Files) -> io::Result<()> {
+    let contents = read_input(&files.input)?;
+    let img = image::load_from_memory(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?.to_luma();
+    let (width, height) = img.dimensions();
+    // The decoder refuses anything larger, don't write a file it can't read
+    if width as u64 * height as u64 > bilevel::MAX_PIXELS {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Image of {}x{} pixels is too large, at most {} pixels are supported", width, height, bilevel::MAX_PIXELS)));
+    }
 
-        high.assign_round(low.clone() + (range.clone() * bounds.get(&c).unwrap().1.clone()), Round::Down);
-        low.assign_round(low.clone() + (range.clone() * bounds.get(&c).unwrap().0.clone()), Round::Up);
-        //println!("{:?} : {:?}, {:?}, {:?}, {:?}, {:?}, ", c, low.prec(), high.prec(), range, high, low); 
+    // Halftones are 0 or 255 already, anything else is thresholded
+    if img.pixels().any(|p| p[0] != 0 && p[0] != 255) {
+        eprintln!("Warning : the image is not bilevel, it is thresholded at 128");
+    }
+    let pixels : Vec<u8> = img.pixels().map(|p| if p[0] < 128 { 1 } else { 0 }).collect();
+
+    let payload = bilevel::encode(&pixels, width as usize, height as usize);
+    let bilevel = Bilevel { width, height, payload, checksum : container::crc32(&pixels) };
+    let mut compressed : Vec<u8> = vec![];
+    bilevel.write(&mut compressed)?;
+
+    if files.stats {
+        let packed = pixels.len().div_ceil(8);
+        eprintln!("before : {} bytes, packed bitmap : {} bytes, after : {} bytes", contents.len(), packed, compressed.len());
+        eprintln!("Bits per pixel : {}", 8.0 * bilevel.payload.len() as f64 / pixels.len() as f64);
+        eprintln!("Compression ratio : {}, against the packed bitmap : {}", compressed.len() as f64 / contents.len() as f64, compressed.len() as f64 / packed as f64);
     }
 
-    return (high + low)/2;
+    write_output(&files.output, &compressed)
 }
 
-fn arithmetic_decode(encoded : &Float, bounds: &HashMap<char, (Float, Float)>, filesize : u32, step_precision: u32) -> String {
-    let mut result : String = String::new();
-    let mut data = encoded.clone();
+fn decode_image(files : &Files) -> io::Result<()> {
+    let compressed = read_input(&files.input)?;
+    let bilevel = Bilevel::read(&mut &compressed[..])?;
 
-    // Initial precision is 32, so get back to that
-    while data.prec() > 32 {
-        for(c, bounds) in bounds {
-            if bounds.1 > data && bounds.0 < data {
-                println!("{:?} : {:?} at prec {:?}, bounds are {:?} - {:?}", c, data, data.prec(), bounds.0.clone(), bounds.1.clone());
-                result.push(*c);
-                data.assign_round((data.clone() - bounds.0.clone()) / (bounds.1.clone() - bounds.0.clone()), Round::Up);
-                data.set_prec(data.prec() - step_precision);
-                break;
-            }
-        }
+    let pixels = bilevel::decode(&bilevel.payload, bilevel.width as usize, bilevel.height as usize);
+    bilevel.verify(&pixels)?;
+
+    let gray : Vec<u8> = pixels.iter().map(|&p| if p == 1 { 0 } else { 255 }).collect();
+    let img = image::DynamicImage::ImageLuma8(image::ImageBuffer::from_vec(bilevel.width, bilevel.height, gray).unwrap());
+    let mut png : Vec<u8> = vec![];
+    img.write_to(&mut png, image::ImageOutputFormat::PNG).map_err(|e| io::Error::other(e.to_string()))?;
+
+    if files.stats {
+        eprintln!("before : {} bytes, after : {} bytes as PNG", compressed.len(), png.len());
     }
 
-    return result;
+    write_output(&files.output, &png)
 }
 
 fn main() {
     // Parse arguments
     let opt = Opt::from_args();
 
-    println!("Reading file");
-    let contents = fs::read_to_string(opt.file).unwrap();
-    let filesize = contents.len() as u32;
+    let result = match &opt {
+        Opt::Encode { files, model, order, coder, max_code_length, method, window_bits } => encode(files, method, coder, model, *order, *max_code_length, *window_bits),
+        Opt::Bench { input } => bench(input),
+        Opt::Decode(files) => decode(files),
+        Opt::EncodeImage(files) => encode_image(files),
+        Opt::DecodeImage(files) => decode_image(files)
+    };
+    if let Err(e) = result {
+        eprintln!("Error : {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Compress, serialize, parse back and decompress
+    fn method_round_trip(contents : &[u8], method : &str, coder : &str, model : &str, order : u8) -> Vec<u8> {
+        let mut compressed : Vec<u8> = vec![];
+        compress(contents, method, coder, model, order, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS).unwrap().write(&mut compressed).unwrap();
+        let container = Container::read(&mut &compressed[..]).unwrap();
+        decompress(&container).unwrap()
+    }
 
-    // https://stackoverflow.com/questions/7150035/ and add a small pad :)
-    let step_precision = (filesize as f64).log2().ceil() as u32;
-    println!("Step precision : {:?}", step_precision);
+    fn round_trip(contents : &[u8], coder : &str, model : &str, order : u8) -> Vec<u8> {
+        method_round_trip(contents, "none", coder, model, order)
+    }
 
-    // Find frequency
-    let frequencies = find_frequencies(&contents);
-    let bounds = find_bounds(&frequencies, filesize, step_precision);
-    println!("{:?}\n{:?}", frequencies.clone(), bounds.clone());
+    proptest! {
+        #[test]
+        fn static_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(round_trip(&contents, "arithmetic", "static", 0), contents);
+        }
 
-    // Encode file
-    let encoded = arithmetic_encode(&contents, &bounds, step_precision);
-    println!("encoder result : {:?}", encoded);
+        // Few symbols with very uneven counts, so that the intervals get scaled and some end up tiny
+        #[test]
+        fn static_skewed_round_trip(contents in "(a{0,50}b?c?){0,400}") {
+            prop_assert_eq!(round_trip(contents.as_bytes(), "arithmetic", "static", 0), contents.as_bytes());
+        }
 
-    // Compute compression ratio
-    let ratio = compression_ratio(&contents, &encoded);
-    println!("Compression ratio : {:?}", ratio);
+        #[test]
+        fn rans_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(round_trip(&contents, "rans", "static", 0), contents);
+        }
 
-    // Decode file
-    let decoded = arithmetic_decode(&encoded, &bounds, filesize, step_precision);
-    println!("decoder result : {:?}", decoded);
+        #[test]
+        fn rans_skewed_round_trip(contents in "(a{0,50}b?c?){0,400}") {
+            prop_assert_eq!(round_trip(contents.as_bytes(), "rans", "static", 0), contents.as_bytes());
+        }
+
+        #[test]
+        fn huffman_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(round_trip(&contents, "huffman", "static", 0), contents);
+        }
 
-    println!("Saving result");
-    fs::write("output.txt", &decoded[..]).unwrap();
+        // Counts growing like Fibonacci numbers give the deepest Huffman trees, so the length limit kicks in
+        #[test]
+        fn huffman_limited_round_trip(symbols in 2usize..24, max_code_length in 8u8..=15) {
+            let (mut a, mut b) = (1usize, 1usize);
+            let mut contents : Vec<u8> = vec![];
+            for s in 0..symbols {
+                contents.extend(std::iter::repeat_n(s as u8, a));
+                (a, b) = (b, a + b);
+            }
+            let mut compressed : Vec<u8> = vec![];
+            compress(&contents, "none", "huffman", "static", 0, max_code_length, lz::WINDOW_BITS).unwrap().write(&mut compressed).unwrap();
+            let container = Container::read(&mut &compressed[..]).unwrap();
+            prop_assert!(container.payload[..huffman::LENGTHS_BYTES].iter().all(|&b| b >> 4 <= max_code_length && b & 15 <= max_code_length));
+            prop_assert_eq!(decompress(&container).unwrap(), contents);
+        }
+
+        #[test]
+        fn adaptive_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(round_trip(&contents, "arithmetic", "adaptive", 0), contents);
+        }
+
+        #[test]
+        fn ppm_round_trip(contents in "[a-e ]{0,2000}", order in 0u8..=4) {
+            prop_assert_eq!(round_trip(contents.as_bytes(), "arithmetic", "ppm", order), contents.as_bytes());
+        }
+
+        // Runs longer than a run-length count, and every coder after the transform
+        #[test]
+        fn bwt_round_trip(contents in "(a{0,300}b?[c-e ]{0,3}){0,20}", coder in prop::sample::select(vec!["arithmetic", "rans", "huffman"])) {
+            prop_assert_eq!(method_round_trip(contents.as_bytes(), "bwt", coder, "static", 0), contents.as_bytes());
+        }
 
-    
+        #[test]
+        fn bwt_binary_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(method_round_trip(&contents, "bwt", "arithmetic", "adaptive", 0), contents);
+        }
+
+        // Small windows, so that matches reach past them and get cut at their edge
+        #[test]
+        fn lz_round_trip(contents in "([a-c]{1,8}|(abcabcab){1,40}){0,300}", window_bits in 4u8..=12) {
+            let mut compressed : Vec<u8> = vec![];
+            compress(contents.as_bytes(), "lz", "arithmetic", "static", 0, huffman::MAX_CODE_LENGTH, window_bits).unwrap().write(&mut compressed).unwrap();
+            let container = Container::read(&mut &compressed[..]).unwrap();
+            prop_assert_eq!(decompress(&container).unwrap(), contents.as_bytes());
+        }
+
+        #[test]
+        fn lz_binary_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000)) {
+            prop_assert_eq!(method_round_trip(&contents, "lz", "arithmetic", "static", 0), contents);
+        }
+
+        #[test]
+        fn truncated_payload_terminates(contents in proptest::collection::vec(any::<u8>(), 1..500), cut in any::<prop::sample::Index>()) {
+            let mut container = compress(&contents, "none", "arithmetic", "static", 0, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS).unwrap();
+            let length = cut.index(container.payload.len());
+            container.payload.truncate(length);
+            // Terminates, and either still decodes or reports the damage
+            if let Ok(decoded) = decompress(&container) { prop_assert_eq!(decoded, contents); }
+        }
+    }
+
+    proptest! {
+        // The mixing model allocates large tables, so fewer cases
+        #![proptest_config(ProptestConfig::with_cases(16))]
+        #[test]
+        fn mixing_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000), order in 0u8..=3) {
+            prop_assert_eq!(round_trip(&contents, "arithmetic", "mix", order), contents);
+        }
+    }
+
+    // A damaged header claiming more data than the payload holds fails cleanly, without decoding that much
+    #[test]
+    fn oversized_length_is_rejected() {
+        let contents = include_bytes!("../lorem-ipsum.txt");
+        for coder in ["rans", "huffman"] {
+            let mut container = compress(contents, "none", coder, "static", 0, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS).unwrap();
+            container.coded_length = 1 << 34;
+            assert_eq!(decompress(&container).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn identical_output_across_runs() {
+        let contents = include_bytes!("../lorem-ipsum.txt");
+        let mut first : Vec<u8> = vec![];
+        let mut second : Vec<u8> = vec![];
+        compress(contents, "none", "arithmetic", "static", 0, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS).unwrap().write(&mut first).unwrap();
+        compress(contents, "none", "arithmetic", "static", 0, huffman::MAX_CODE_LENGTH, lz::WINDOW_BITS).unwrap().write(&mut second).unwrap();
+        assert_eq!(first, second);
+    }
 }
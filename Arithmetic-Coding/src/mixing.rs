@@ -0,0 +1,187 @@
+use std::io::{Read, Write};
+use crate::coder::{self, ArithmeticEncoder, ArithmeticDecoder};
+use crate::model::Model;
+use crate::EOF;
+
+// Probabilities are 12-bit integers, the coder total for every bit
+const PROBABILITY_BITS : u32 = 12;
+const ONE : i32 = 1 << PROBABILITY_BITS;
+
+// Size of the hashed table of every order above 0
+const TABLE_BITS : u32 = 20;
+
+// Largest supported order, with order 0 there is one more input to the mixer
+pub const MAX_ORDER : usize = 7;
+
+// Adaptation of the counters slows down until this many observations
+const COUNT_LIMIT : u32 = 255;
+
+// Mixer learning rate
+const LEARNING_RATE : i32 = 6;
+
+// Logistic function, d and the result in fixed point, d with 8 fractional bits over -2047..2047
+// Integer interpolation in a table, so that every machine computes the same probabilities
+fn squash(d : i32) -> i32 {
+    static TABLE : [i32; 33] = [
+        1, 2, 3, 6, 10, 16, 27, 45, 73, 120, 194, 310, 488, 747, 1101, 1546,
+        2047, 2549, 2994, 3348, 3607, 3785, 3901, 3975, 4022, 4050, 4068, 4079, 4085, 4089, 4092, 4093, 4094
+    ];
+    if d > 2047 { return ONE - 1; }
+    if d < -2047 { return 1; }
+    let w = d & 127;
+    let i = ((d >> 7) + 16) as usize;
+    (TABLE[i]*(128 - w) + TABLE[i + 1]*w + 64) >> 7
+}
+
+// Inverse of squash, tabulated for every probability
+fn stretch_table() -> Vec<i32> {
+    let mut table = vec![0; ONE as usize];
+    let mut pi = 0;
+    for x in -2047..=2047 {
+        let v = squash(x);
+        for p in pi..=v {
+            table[p as usize] = x;
+        }
+        pi = v + 1;
+    }
+    for p in pi..ONE {
+        table[p as usize] = 2047;
+    }
+    table
+}
+
+// Probability that the next bit is 1, and how often it was updated
+// Stored as 22 bits of probability above 10 bits of count
+fn counter_probability(counter : u32) -> i32 {
+    (counter >> (10 + 22 - PROBABILITY_BITS)) as i32
+}
+
+fn counter_update(counter : &mut u32, bit : u32) {
+    let count = *counter & 1023;
+    let p = (*counter >> 10) as i64;
+    let target = (bit as i64) << 22;
+    // Rate 1/(count + 1.5), fast at first then settling
+    let p = p + (target - p) * 2 / (2*count as i64 + 3);
+    *counter = ((p as u32) << 10) | (count + 1).min(COUNT_LIMIT);
+}
+
+fn hash(value : u64) -> u32 {
+    let value = value.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (value >> 32) as u32
+}
+
+// Bitwise context mixing, as in the PAQ family of compressors
+// Every bit of a byte is predicted by one model per order, indexed by the previous bytes and the bits of the current byte so far
+// The predictions are mixed in the logistic domain with weights trained online to minimize the coding cost
+pub struct MixingModel {
+    order : usize,
+    // One table per order, order 0 is indexed directly
+    tables : Vec<Vec<u32>>,
+    // Hash of the previous bytes for every order, fixed during a byte
+    contexts : Vec<u32>,
+    history : u64,
+    weights : Vec<i32>,
+    stretch : Vec<i32>,
+    // Inputs of the current bit, kept for the update
+    inputs : Vec<i32>,
+    indices : Vec<usize>
+}
+
+impl MixingModel {
+    pub fn new(order : usize) -> MixingModel {
+        assert!(order <= MAX_ORDER, "Mixing order is at most {}", MAX_ORDER);
+        let initial = (ONE as u32 / 2) << (10 + 22 - PROBABILITY_BITS);
+        let tables = (0..=order).map(|o| vec![initial; if o == 0 { 256 } else { 1 << TABLE_BITS }]).collect();
+        let mut model = MixingModel {
+            order,
+            tables,
+            contexts : vec![0; order + 1],
+            history : 0,
+            weights : vec![(1 << 16) / (order as i32 + 1); order + 1],
+            stretch : stretch_table(),
+            inputs : vec![0; order + 1],
+            indices : vec![0; order + 1]
+        };
+        model.update_contexts();
+        model
+    }
+
+    fn update_contexts(&mut self) {
+        for o in 1..=self.order {
+            let mask = u64::MAX >> (64 - 8*o);
+            self.contexts[o] = hash(((o as u64) << 56) | (self.history & mask));
+        }
+    }
+
+    // Probability that the next bit is 1, partial holds the bits of the byte so far behind a leading 1
+    fn predict(&mut self, partial : u32) -> u32 {
+        let mut dot : i64 = 0;
+        for o in 0..=self.order {
+            let index = if o == 0 {
+                partial as usize
+            } else {
+                (hash(((self.contexts[o] as u64) << 8) | partial as u64) >> (32 - TABLE_BITS)) as usize
+            };
+            self.indices[o] = index;
+            self.inputs[o] = self.stretch[counter_probability(self.tables[o][index]) as usize];
+            dot += self.inputs[o] as i64 * self.weights[o] as i64;
+        }
+        squash((dot >> 16) as i32).clamp(1, ONE - 1) as u32
+    }
+
+    fn update(&mut self, p : u32, bit : u32) {
+        let error = ((bit as i32) << PROBABILITY_BITS) - p as i32;
+        for o in 0..=self.order {
+            self.weights[o] += (self.inputs[o] * error * LEARNING_RATE) >> 10;
+            counter_update(&mut self.tables[o][self.indices[o]], bit);
+        }
+    }
+
+    fn end_byte(&mut self, byte : u8) {
+        self.history = (self.history << 8) | byte as u64;
+        self.update_contexts();
+    }
+}
+
+// Every byte is preceded by an end of file flag, with a fixed probability of 1 / MAX_TOTAL for the end, about 2e-5 bits per byte
+impl Model for MixingModel {
+    fn encode<W : Write>(&mut self, encoder : &mut ArithmeticEncoder<W>, symbol : usize) {
+        if symbol == EOF as usize {
+            encoder.encode(0, 1, coder::MAX_TOTAL);
+            return;
+        }
+        encoder.encode(1, coder::MAX_TOTAL, coder::MAX_TOTAL);
+
+        let byte = symbol as u8;
+        let mut partial = 1;
+        for i in (0..8).rev() {
+            let bit = ((byte >> i) & 1) as u32;
+            let p = self.predict(partial);
+            // A 1 takes the low part of the interval
+            if bit == 1 { encoder.encode(0, p, ONE as u32); } else { encoder.encode(p, ONE as u32, ONE as u32); }
+            self.update(p, bit);
+            partial = (partial << 1) | bit;
+        }
+        self.end_byte(byte);
+    }
+
+    fn decode<R : Read>(&mut self, decoder : &mut ArithmeticDecoder<R>) -> usize {
+        if decoder.target(coder::MAX_TOTAL) < 1 {
+            decoder.consume(0, 1, coder::MAX_TOTAL);
+            return EOF as usize;
+        }
+        decoder.consume(1, coder::MAX_TOTAL, coder::MAX_TOTAL);
+
+        let mut partial = 1;
+        for _ in 0..8 {
+            let p = self.predict(partial);
+            let bit = if decoder.target(ONE as u32) < p { 1 } else { 0 };
+            if bit == 1 { decoder.consume(0, p, ONE as u32); } else { decoder.consume(p, ONE as u32, ONE as u32); }
+            self.update(p, bit);
+            partial = (partial << 1) | bit;
+        }
+        let byte = partial as u8;
+        self.end_byte(byte);
+        byte as usize
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::{Read, Write};
+use crate::coder::{self, ArithmeticEncoder, ArithmeticDecoder};
+use crate::{Symbol, EOF};
+
+// Probability model driving the arithmetic coder
+// The encoder and the decoder must make the same predictions, so a model only learns from the symbols coded so far
+// Symbols are bytes, or EOF to mark the end of the data
+pub trait Model {
+    // Code symbol, then learn from it
+    fn encode<W : Write>(&mut self, encoder : &mut ArithmeticEncoder<W>, symbol : usize);
+    // Find the next symbol, then learn from it the same way encode did
+    fn decode<R : Read>(&mut self, decoder : &mut ArithmeticDecoder<R>) -> usize;
+}
+
+// Count of every byte present, sorted by symbol, plus EOF once
+pub fn find_frequencies(data : &[u8]) -> Vec<(Symbol, u32)> {
+    let mut counts = [0u32; 256];
+
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let mut frequencies : Vec<(Symbol, u32)> = counts.iter().enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(byte, &count)| (byte as Symbol, count))
+        .collect();
+    frequencies.push((EOF, 1));
+
+    frequencies
+}
+
+// Order-0 model with a fixed table, counted beforehand and sent to the decoder
+// Cumulative frequencies with the symbols in increasing order, cumulative[i] is the sum of the counts of the symbols before symbols[i] and the last entry is the total
+pub struct StaticModel {
+    symbols : Vec<Symbol>,
+    cumulative : Vec<u32>
+}
+
+impl StaticModel {
+    // The table is sorted first, so the intervals only depend on the counts and not on the order they come in
+    // Counts are scaled down when needed so that the total fits the coder precision
+    pub fn new(freqs : &[(Symbol, u32)]) -> StaticModel {
+        let mut freqs = freqs.to_vec();
+        freqs.sort_unstable();
+
+        let filesize : u64 = freqs.iter().map(|&(_, count)| count as u64).sum();
+        let scale = |count : u32| -> u32 {
+            if filesize <= coder::MAX_TOTAL as u64 { count }
+            else { ((count as u64 * coder::MAX_TOTAL as u64 / filesize) as u32).max(1) }
+        };
+
+        let mut cumulative : Vec<u32> = vec![0];
+        let mut prevhigh = 0;
+        for &(_, count) in freqs.iter() {
+            prevhigh += scale(count);
+            cumulative.push(prevhigh);
+        }
+
+        StaticModel { symbols : freqs.iter().map(|&(c, _)| c).collect(), cumulative }
+    }
+
+    fn total(&self) -> u32 {
+        self.cumulative[self.symbols.len()]
+    }
+
+    fn interval(&self, symbol : usize) -> (u32, u32) {
+        let i = self.symbols.binary_search(&(symbol as Symbol)).expect("Symbol missing from the frequency table");
+        (self.cumulative[i], self.cumulative[i + 1])
+    }
+
+    // Symbol whose interval contains target, with that interval
+    fn find(&self, target : u32) -> (usize, (u32, u32)) {
+        let i = self.cumulative.partition_point(|&c| c <= target) - 1;
+        (self.symbols[i] as usize, (self.cumulative[i], self.cumulative[i + 1]))
+    }
+}
+
+impl Model for StaticModel {
+    fn encode<W : Write>(&mut self, encoder : &mut ArithmeticEncoder<W>, symbol : usize) {
+        let (low, high) = self.interval(symbol);
+        encoder.encode(low, high, self.total());
+    }
+
+    fn decode<R : Read>(&mut self, decoder : &mut ArithmeticDecoder<R>) -> usize {
+        let (symbol, (low, high)) = self.find(decoder.target(self.total()));
+        decoder.consume(low, high, self.total());
+        symbol
+    }
+}
+
+// Count added to a symbol every time it is seen
+const INCREMENT : u32 = 32;
+
+// Frequency table updated after every symbol, the same way by the encoder and the decoder, so it never has to be transmitted
+// Cumulative frequencies are kept in a Fenwick tree, for logarithmic lookups and updates
+pub struct AdaptiveModel {
+    frequencies : Vec<u32>,
+    // tree[i] holds the sum of the frequencies in (i - lowbit(i), i], 1-indexed
+    tree : Vec<u32>,
+    total : u32
+}
+
+impl AdaptiveModel {
+    // Every symbol starts with a count of 1, so that it can be coded the first time it shows up
+    pub fn new(symbols : usize) -> AdaptiveModel {
+        let mut model = AdaptiveModel { frequencies : vec![0; symbols], tree : vec![0; symbols + 1], total : 0 };
+        for symbol in 0..symbols {
+            model.add(symbol, 1);
+        }
+        model
+    }
+
+    fn add(&mut self, symbol : usize, delta : u32) {
+        self.frequencies[symbol] += delta;
+        self.total += delta;
+        let mut i = symbol + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Sum of the frequencies of the symbols before symbol
+    fn cumulative(&self, symbol : usize) -> u32 {
+        let mut sum = 0;
+        let mut i = symbol;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+
+    pub fn interval(&self, symbol : usize) -> (u32, u32) {
+        let low = self.cumulative(symbol);
+        (low, low + self.frequencies[symbol])
+    }
+
+    // Symbol whose interval contains target, found by walking down the tree
+    pub fn find(&self, target : u32) -> usize {
+        let mut position = 0;
+        let mut remaining = target;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+        while step > 0 {
+            let next = position + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                position = next;
+                remaining -= self.tree[next];
+            }
+            step >>= 1;
+        }
+        position
+    }
+
+    // Count one more occurrence of symbol, halving every count when the total gets too large for the coder
+    pub fn update(&mut self, symbol : usize) {
+        if self.total + INCREMENT > coder::MAX_TOTAL {
+            let halved : Vec<u32> = self.frequencies.iter().map(|&f| f.div_ceil(2)).collect();
+            self.frequencies = vec![0; halved.len()];
+            self.tree = vec![0; halved.len() + 1];
+            self.total = 0;
+            for (symbol, &f) in halved.iter().enumerate() {
+                self.add(symbol, f);
+            }
+        }
+        self.add(symbol, INCREMENT);
+    }
+
+}
+
+impl Model for AdaptiveModel {
+    fn encode<W : Write>(&mut self, encoder : &mut ArithmeticEncoder<W>, symbol : usize) {
+        let (low, high) = self.interval(symbol);
+        encoder.encode(low, high, self.total);
+        self.update(symbol);
+    }
+
+    fn decode<R : Read>(&mut self, decoder : &mut ArithmeticDecoder<R>) -> usize {
+        let symbol = self.find(decoder.target(self.total));
+        let (low, high) = self.interval(symbol);
+        decoder.consume(low, high, self.total);
+        self.update(symbol);
+        symbol
+    }
+}
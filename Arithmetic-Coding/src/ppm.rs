@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use crate::coder::{ArithmeticEncoder, ArithmeticDecoder};
+use crate::model::Model;
+
+// Largest supported order, so that a context and its order fit in a u64 key
+pub const MAX_ORDER : usize = 7;
+
+// Counts of a context are halved past this total, to stay within the coder precision and follow changes in the statistics
+const MAX_COUNT : u32 = 1 << 14;
+
+// Symbols seen after one context, with their counts
+#[derive(Default)]
+struct Context {
+    symbols : Vec<(usize, u32)>,
+    total : u32
+}
+
+impl Context {
+    fn add(&mut self, symbol : usize) {
+        match self.symbols.iter_mut().find(|(s, _)| *s == symbol) {
+            Some((_, count)) => *count += 1,
+            None => self.symbols.push((symbol, 1))
+        }
+        self.total += 1;
+
+        if self.total > MAX_COUNT {
+            for (_, count) in self.symbols.iter_mut() {
+                *count = count.div_ceil(2);
+            }
+            self.total = self.symbols.iter().map(|&(_, count)| count).sum();
+        }
+    }
+}
+
+// Prediction by partial matching, from Cleary & Witten, "Data compression using adaptive coding and partial string matching"
+// A symbol is coded in the longest context where it was seen before, escaping to shorter contexts until then
+// Escapes are counted as in method C, with exclusion of the symbols already ruled out and update exclusion
+pub struct PpmModel {
+    order : usize,
+    alphabet : usize,
+    contexts : HashMap<u64, Context>,
+    // Previous bytes, the most recent one in the low byte
+    history : u64,
+    // Number of previous bytes, up to order
+    seen : usize,
+    excluded : Vec<bool>
+}
+
+impl PpmModel {
+    pub fn new(alphabet : usize, order : usize) -> PpmModel {
+        assert!(order <= MAX_ORDER, "PPM order is at most {}", MAX_ORDER);
+        PpmModel { order, alphabet, contexts : HashMap::new(), history : 0, seen : 0, excluded : vec![false; alphabet] }
+    }
+
+    fn key(&self, order : usize) -> u64 {
+        let mask = if order == 0 { 0 } else { u64::MAX >> (64 - 8*order) };
+        ((order as u64) << 56) | (self.history & mask)
+    }
+
+    // Count symbol in the contexts from order up, then shift it into the history
+    fn update(&mut self, symbol : usize, order : usize) {
+        for order in order..=self.seen {
+            let key = self.key(order);
+            self.contexts.entry(key).or_default().add(symbol);
+        }
+        self.history = (self.history << 8) | (symbol as u8 as u64);
+        self.seen = (self.seen + 1).min(self.order);
+    }
+}
+
+impl Model for PpmModel {
+    fn encode<W : Write>(&mut self, encoder : &mut ArithmeticEncoder<W>, symbol : usize) {
+        self.excluded.iter_mut().for_each(|e| *e = false);
+
+        for order in (0..=self.seen).rev() {
+            let context = match self.contexts.get(&self.key(order)) {
+                Some(context) => context,
+                None => continue
+            };
+
+            let mut total = 0;
+            let mut found = None;
+            for &(s, count) in context.symbols.iter().filter(|(s, _)| !self.excluded[*s]) {
+                if s == symbol { found = Some((total, total + count)); }
+                total += count;
+            }
+            // Every symbol of this context was ruled out already, the decoder knows it too
+            if total == 0 { continue; }
+
+            let escape = context.symbols.len() as u32;
+            match found {
+                Some((low, high)) => {
+                    encoder.encode(low, high, total + escape);
+                    self.update(symbol, order);
+                    return;
+                },
+                None => {
+                    encoder.encode(total, total + escape, total + escape);
+                    for &(s, _) in context.symbols.iter() { self.excluded[s] = true; }
+                }
+            }
+        }
+
+        // Order -1, every symbol not excluded yet is equally likely
+        let low = self.excluded[..symbol].iter().filter(|&&e| !e).count() as u32;
+        let total = self.excluded.iter().filter(|&&e| !e).count() as u32;
+        encoder.encode(low, low + 1, total);
+        self.update(symbol, 0);
+    }
+
+    fn decode<R : Read>(&mut self, decoder : &mut ArithmeticDecoder<R>) -> usize {
+        self.excluded.iter_mut().for_each(|e| *e = false);
+
+        for order in (0..=self.seen).rev() {
+            let context = match self.contexts.get(&self.key(order)) {
+                Some(context) => context,
+                None => continue
+            };
+
+            let total : u32 = context.symbols.iter().filter(|(s, _)| !self.excluded[*s]).map(|&(_, count)| count).sum();
+            if total == 0 { continue; }
+
+            let escape = context.symbols.len() as u32;
+            let target = decoder.target(total + escape);
+            if target >= total {
+                decoder.consume(total, total + escape, total + escape);
+                for &(s, _) in context.symbols.iter() { self.excluded[s] = true; }
+                continue;
+            }
+
+            let mut low = 0;
+            for &(s, count) in context.symbols.iter().filter(|(s, _)| !self.excluded[*s]) {
+                if target < low + count {
+                    decoder.consume(low, low + count, total + escape);
+                    self.update(s, order);
+                    return s;
+                }
+                low += count;
+            }
+        }
+
+        let total = self.excluded.iter().filter(|&&e| !e).count() as u32;
+        let target = decoder.target(total) as usize;
+        let symbol = (0..self.alphabet).filter(|&s| !self.excluded[s]).nth(target).unwrap();
+        let low = target as u32;
+        decoder.consume(low, low + 1, total);
+        self.update(symbol, 0);
+        symbol
+    }
+}
@@ -0,0 +1,109 @@
+// Range asymmetric numeral systems, from Duda, "Asymmetric numeral systems: entropy coding combining speed of Huffman coding with compression rate of arithmetic coding"
+// Byte-wise renormalization as in Giesen's rans_byte, decoding looks symbols up in a table of every slot
+// The encoder works backwards through the data so that the decoder reads forwards
+
+// Frequencies are normalized to a total of 1 << SCALE_BITS
+const SCALE_BITS : u32 = 14;
+const SCALE : u32 = 1 << SCALE_BITS;
+
+// Lower bound of the normalized state
+const RANS_L : u32 = 1 << 23;
+
+pub struct RansTable {
+    // Indexed by symbol, 0 for symbols absent from the table
+    frequencies : Vec<u32>,
+    cumulative : Vec<u32>,
+    // Symbol owning every slot of the total
+    slots : Vec<u16>
+}
+
+// Scale counts, sorted by symbol, to sum exactly to SCALE while keeping every symbol present
+// Rounding errors are settled on the most frequent symbols, where they cost the least
+fn normalize(freqs : &[(u16, u32)]) -> Vec<(u16, u32)> {
+    let total : u64 = freqs.iter().map(|&(_, count)| count as u64).sum();
+    let mut scaled : Vec<(u16, u32)> = freqs.iter()
+        .map(|&(symbol, count)| (symbol, ((count as u64 * SCALE as u64 / total) as u32).max(1)))
+        .collect();
+
+    let mut sum : u32 = scaled.iter().map(|&(_, count)| count).sum();
+    while sum != SCALE {
+        // First of the largest counts, so that both sides pick the same one
+        let largest = (0..scaled.len()).fold(0, |best, i| if scaled[i].1 > scaled[best].1 { i } else { best });
+        if sum < SCALE {
+            scaled[largest].1 += SCALE - sum;
+            sum = SCALE;
+        } else {
+            let taken = (sum - SCALE).min(scaled[largest].1 - 1);
+            scaled[largest].1 -= taken;
+            sum -= taken;
+        }
+    }
+    scaled
+}
+
+impl RansTable {
+    // Built from the symbol counts of find_frequencies, alphabet is the number of possible symbols
+    pub fn new(freqs : &[(u16, u32)], alphabet : usize) -> RansTable {
+        let mut frequencies = vec![0; alphabet];
+        let mut cumulative = vec![0; alphabet];
+        let mut slots : Vec<u16> = Vec::with_capacity(SCALE as usize);
+
+        let mut freqs = freqs.to_vec();
+        freqs.sort_unstable();
+        for (symbol, count) in normalize(&freqs) {
+            frequencies[symbol as usize] = count;
+            cumulative[symbol as usize] = slots.len() as u32;
+            slots.extend(std::iter::repeat_n(symbol, count as usize));
+        }
+
+        RansTable { frequencies, cumulative, slots }
+    }
+
+    pub fn encode(&self, data : &[u8]) -> Vec<u8> {
+        // Bytes come out in reverse, they are flipped at the end
+        let mut output : Vec<u8> = vec![];
+        let mut state = RANS_L;
+
+        for &byte in data.iter().rev() {
+            let frequency = self.frequencies[byte as usize];
+            let cumulative = self.cumulative[byte as usize];
+            assert!(frequency > 0, "Symbol {} missing from the frequency table", byte);
+
+            // Renormalize so that the state stays below 2^32 after coding the symbol
+            let state_max = ((RANS_L >> SCALE_BITS) << 8) * frequency;
+            while state >= state_max {
+                output.push(state as u8);
+                state >>= 8;
+            }
+            state = ((state / frequency) << SCALE_BITS) + (state % frequency) + cumulative;
+        }
+
+        output.extend_from_slice(&state.to_le_bytes());
+        output.reverse();
+        output
+    }
+
+    // Decode length symbols, None when the input runs out first
+    // A valid stream ends with the state back at RANS_L, so it never needs bytes past its end
+    pub fn decode(&self, encoded : &[u8], length : u64) -> Option<Vec<u8>> {
+        let mut input = encoded.iter().copied();
+        let mut state = 0u32;
+        for _ in 0..4 {
+            state = (state << 8) | input.next()? as u32;
+        }
+
+        let mut result : Vec<u8> = vec![];
+        for _ in 0..length {
+            let slot = state & (SCALE - 1);
+            let symbol = self.slots[slot as usize];
+            result.push(symbol as u8);
+
+            state = self.frequencies[symbol as usize] * (state >> SCALE_BITS) + slot - self.cumulative[symbol as usize];
+            while state < RANS_L {
+                state = (state << 8) | input.next()? as u32;
+            }
+        }
+
+        Some(result)
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::{self, Write};
+use crate::container::{self, Container};
+
+// Shannon entropy of the bytes of data, in bits per byte, the bound for any order-0 model
+pub fn entropy(data : &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let total = data.len() as f64;
+    counts.iter().filter(|&&c| c > 0).fold(0.0, |sum, &c| {
+        let p = c as f64 / total;
+        sum - p * p.log2()
+    })
+}
+
+// Where the bits of a compressed file go
+pub struct SizeReport {
+    // Size of the original data, in bytes, each one a symbol
+    pub symbols : u64,
+    // Coded data
+    pub payload_bits : u64,
+    // What the decoder needs to rebuild the model, frequency table or code lengths
+    pub model_bits : u64,
+    // Fixed fields of the container, magic, lengths and checksum
+    pub header_bits : u64,
+    pub total_bytes : u64,
+    // Order-0 entropy of the original data, in bits per symbol
+    pub entropy : f64
+}
+
+impl SizeReport {
+    // model_in_payload is the number of payload bytes holding the model rather than coded data
+    pub fn new(original : &[u8], container : &Container, model_in_payload : usize) -> SizeReport {
+        let table_bytes = container.table_bytes() as u64;
+        SizeReport {
+            symbols : original.len() as u64,
+            payload_bits : 8 * (container.payload.len() - model_in_payload) as u64,
+            model_bits : 8 * (table_bytes - 2 + model_in_payload as u64),
+            // The entry count of the table is a header field too
+            header_bits : 8 * (container::FIXED_BYTES as u64 + 2),
+            total_bytes : (container::FIXED_BYTES + container.table_bytes() + container.payload.len()) as u64,
+            entropy : entropy(original)
+        }
+    }
+
+    // The ratios are None for empty data, which has no symbol to divide by
+    pub fn ratio(&self) -> Option<f64> {
+        self.per_symbol(self.total_bytes as f64)
+    }
+
+    // Bits per symbol of the whole file
+    pub fn bits_per_symbol(&self) -> Option<f64> {
+        self.per_symbol(8.0 * self.total_bytes as f64)
+    }
+
+    fn per_symbol(&self, value : f64) -> Option<f64> {
+        if self.symbols == 0 { None } else { Some(value / self.symbols as f64) }
+    }
+
+    pub fn write<W : Write>(&self, out : &mut W) -> io::Result<()> {
+        let entropy_bits = self.entropy * self.symbols as f64;
+        writeln!(out, "original      : {} bytes", self.symbols)?;
+        writeln!(out, "payload       : {} bits", self.payload_bits)?;
+        writeln!(out, "model         : {} bits", self.model_bits)?;
+        writeln!(out, "header        : {} bits", self.header_bits)?;
+        writeln!(out, "total         : {} bytes, ratio {}", self.total_bytes, format_ratio(self.ratio()))?;
+        writeln!(out, "bits / symbol : {}, payload alone {}", format_ratio(self.bits_per_symbol()),
+            format_ratio(self.per_symbol(self.payload_bits as f64)))?;
+        writeln!(out, "entropy       : {:.4} bits / symbol, {:.0} bytes for an ideal order-0 coder", self.entropy, (entropy_bits / 8.0).ceil())?;
+        if entropy_bits > 0.0 {
+            writeln!(out, "payload is {:+.2} % from the order-0 entropy", 100.0 * (self.payload_bits as f64 / entropy_bits - 1.0))?;
+        }
+        Ok(())
+    }
+}
+
+// A ratio with 4 decimals, or n/a when there was nothing to divide by
+pub fn format_ratio(ratio : Option<f64>) -> String {
+    ratio.map_or("n/a".to_string(), |ratio| format!("{:.4}", ratio))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_input() {
+        let container = Container {
+            model : container::MODEL_ADAPTIVE, order : 0, method : container::METHOD_NONE,
+            original_length : 0, coded_length : 0, frequencies : vec![], payload : vec![0], checksum : container::crc32(&[])
+        };
+        let report = SizeReport::new(&[], &container, 0);
+        assert_eq!((report.ratio(), report.bits_per_symbol()), (None, None));
+
+        let mut out : Vec<u8> = vec![];
+        report.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("ratio n/a") && !out.contains("inf") && !out.contains("NaN"), "{}", out);
+    }
+}
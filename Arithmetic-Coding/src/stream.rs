@@ -0,0 +1,111 @@
+use std::io::{self, Read, Write};
+use crate::coder::{ArithmeticEncoder, ArithmeticDecoder};
+use crate::model::Model;
+use crate::EOF;
+
+// Compresses the bytes written to it with model, into output
+// finish must be called to mark the end of the data and flush the last bits
+pub struct Encoder<W : Write, M : Model> {
+    coder : ArithmeticEncoder<W>,
+    model : M
+}
+
+impl<W : Write, M : Model> Encoder<W, M> {
+    pub fn new(output : W, model : M) -> Encoder<W, M> {
+        Encoder { coder : ArithmeticEncoder::new(output), model }
+    }
+
+    // Code the end of file symbol, flush and give the writer back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.model.encode(&mut self.coder, EOF as usize);
+        self.coder.finish()
+    }
+}
+
+impl<W : Write, M : Model> Write for Encoder<W, M> {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.model.encode(&mut self.coder, byte as usize);
+        }
+        self.coder.take_error()?;
+        Ok(buf.len())
+    }
+
+    // Bits are only known once the interval narrows enough, they go out with finish
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Decompresses the data read from input, with the same model the encoder used
+// A damaged input may never reach the end of file symbol, callers that know the length should read no more than that
+pub struct Decoder<R : Read, M : Model> {
+    coder : ArithmeticDecoder<R>,
+    model : M,
+    finished : bool
+}
+
+impl<R : Read, M : Model> Decoder<R, M> {
+    pub fn new(input : R, model : M) -> Decoder<R, M> {
+        Decoder { coder : ArithmeticDecoder::new(input), model, finished : false }
+    }
+}
+
+impl<R : Read, M : Model> Read for Decoder<R, M> {
+    fn read(&mut self, buf : &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() && !self.finished {
+            let symbol = self.model.decode(&mut self.coder);
+            if symbol == EOF as usize {
+                self.finished = true;
+            } else {
+                buf[n] = symbol as u8;
+                n += 1;
+            }
+        }
+        self.coder.take_error()?;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AdaptiveModel;
+    use crate::ppm::PpmModel;
+    use proptest::prelude::*;
+
+    proptest! {
+        // Written and read back in chunks of any size, the stream doesn't depend on how the data is split
+        #[test]
+        fn chunked_round_trip(contents in proptest::collection::vec(any::<u8>(), 0..2000), chunk in 1usize..300) {
+            let mut encoder = Encoder::new(vec![], PpmModel::new(EOF as usize + 1, 2));
+            for part in contents.chunks(chunk) {
+                encoder.write_all(part).unwrap();
+            }
+            let encoded = encoder.finish().unwrap();
+
+            let mut decoder = Decoder::new(&encoded[..], PpmModel::new(EOF as usize + 1, 2));
+            let mut decoded : Vec<u8> = vec![];
+            let mut buffer = vec![0; chunk];
+            loop {
+                let n = decoder.read(&mut buffer).unwrap();
+                if n == 0 { break; }
+                decoded.extend_from_slice(&buffer[..n]);
+            }
+            prop_assert_eq!(decoded, contents);
+        }
+    }
+
+    #[test]
+    fn read_error_is_reported() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _ : &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("unplugged"))
+            }
+        }
+        let mut decoder = Decoder::new(Failing, AdaptiveModel::new(EOF as usize + 1));
+        assert!(decoder.read(&mut [0; 16]).is_err());
+    }
+}
@@ -100,9 +100,11 @@ fn main() {
     println!("MSE ST : {}", img_quality::mse(&img, &output_st).unwrap());
     println!("MSE FS : {}", img_quality::mse(&img, &output_fs).unwrap());
 
-    println!("HPSNR FS : {}", img_quality::hpsnr(&img, &output_fs).unwrap());
-    println!("HPSNR JA : {}", img_quality::hpsnr(&img, &output_ja).unwrap());
-    println!("HPSNR ST : {}", img_quality::hpsnr(&img, &output_st).unwrap());
+    let hvs_model = img_quality::HvsModel::default();
+    let border = img_quality::BorderMode::Mirror;
+    println!("HPSNR FS : {}", img_quality::hpsnr(&img, &output_fs, &hvs_model, border).unwrap());
+    println!("HPSNR JA : {}", img_quality::hpsnr(&img, &output_ja, &hvs_model, border).unwrap());
+    println!("HPSNR ST : {}", img_quality::hpsnr(&img, &output_st, &hvs_model, border).unwrap());
 
 
 
@@ -214,9 +214,11 @@ fn main() {
     output_16.save("./output16.png").unwrap();
     output_32.save("./output32.png").unwrap();
 
-    println!("HPSNR 4x4 : {}", img_quality::hpsnr(&img, &output_4).unwrap());
-    println!("HPSNR 8x8 : {}", img_quality::hpsnr(&img, &output_8).unwrap());
-    println!("HPSNR 16x16 : {}", img_quality::hpsnr(&img, &output_16).unwrap());
-    println!("HPSNR 32x32 : {}", img_quality::hpsnr(&img, &output_32).unwrap());
+    let hvs_model = img_quality::HvsModel::default();
+    let border = img_quality::BorderMode::Mirror;
+    println!("HPSNR 4x4 : {}", img_quality::hpsnr(&img, &output_4, &hvs_model, border).unwrap());
+    println!("HPSNR 8x8 : {}", img_quality::hpsnr(&img, &output_8, &hvs_model, border).unwrap());
+    println!("HPSNR 16x16 : {}", img_quality::hpsnr(&img, &output_16, &hvs_model, border).unwrap());
+    println!("HPSNR 32x32 : {}", img_quality::hpsnr(&img, &output_32, &hvs_model, border).unwrap());
     
 }
@@ -91,8 +91,10 @@ fn main() {
 
     println!("Classical MSE : {}", img_quality::mse(&img, &classical).unwrap());
     println!("Bayer MSE : {}", img_quality::mse(&img, &bayer).unwrap());
-    println!("Classical HPSNR : {}", img_quality::hpsnr(&img, &classical).unwrap());
-    println!("Bayer HPSNR : {}", img_quality::hpsnr(&img, &bayer).unwrap());
+    let hvs_model = img_quality::HvsModel::default();
+    let border = img_quality::BorderMode::Mirror;
+    println!("Classical HPSNR : {}", img_quality::hpsnr(&img, &classical, &hvs_model, border).unwrap());
+    println!("Bayer HPSNR : {}", img_quality::hpsnr(&img, &bayer, &hvs_model, border).unwrap());
 
 
     println!("Saving result");
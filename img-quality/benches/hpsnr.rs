@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
+
+// Gradient and its thresholded version, a worst case for the filter since every pixel differs
+fn test_images(size : u32) -> (image::DynamicImage, image::DynamicImage) {
+    let original = image::ImageBuffer::from_fn(size, size, |x, y| image::Luma([((x + y) % 256) as u8]));
+    let halftone = image::ImageBuffer::from_fn(size, size, |x, y| image::Luma([if (x*7 + y*13) % 256 < (x + y) % 256 { 255 } else { 0 }]));
+    (image::DynamicImage::ImageLuma8(original), image::DynamicImage::ImageLuma8(halftone))
+}
+
+fn bench_hpsnr(c : &mut Criterion) {
+    let mut group = c.benchmark_group("hpsnr");
+    group.sample_size(10);
+
+    let model = img_quality::HvsModel::default();
+    for &size in [256, 512].iter() {
+        let (original, halftone) = test_images(size);
+        group.bench_with_input(BenchmarkId::new("direct", size), &size, |b, _| b.iter(|| img_quality::hpsnr_direct(&original, &halftone, &model, img_quality::BorderMode::Mirror).unwrap()));
+        group.bench_with_input(BenchmarkId::new("separable", size), &size, |b, _| b.iter(|| img_quality::hpsnr(&original, &halftone, &model, img_quality::BorderMode::Mirror).unwrap()));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_hpsnr);
+criterion_main!(benches);
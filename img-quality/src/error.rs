@@ -0,0 +1,76 @@
+use std::fmt;
+use image::GenericImageView;
+
+// Reasons why two images can't be compared
+#[derive(Debug, Clone, PartialEq)]
+pub enum QualityError {
+    // Images don't have the same width and height
+    DimensionMismatch { original : (u32, u32), new : (u32, u32) },
+    // Images don't have the same channels or bit depth
+    ColorTypeMismatch { original : image::ColorType, new : image::ColorType },
+    // Images have no pixel
+    EmptyImage,
+    // Images are too small for the metric, e.g. smaller than its filter
+    ImageTooSmall { dimensions : (u32, u32), minimum : (u32, u32) },
+    // A parameter of the metric is out of its range, e.g. a filter of size 0 or a negative resolution
+    InvalidParameter { name : &'static str, value : f64 }
+}
+
+impl fmt::Display for QualityError { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+        QualityError::DimensionMismatch { original, new } => write!(f, "Size doesn't match : {:?} vs {:?}", original, new),
+        QualityError::ColorTypeMismatch { original, new } => write!(f, "Color type doesn't match : {:?} vs {:?}", original, new),
+        QualityError::EmptyImage => write!(f, "Image is empty"),
+        QualityError::ImageTooSmall { dimensions, minimum } => write!(f, "Image too small : {:?}, need at least {:?}", dimensions, minimum),
+        QualityError::InvalidParameter { name, value } => write!(f, "Invalid {} : {}", name, value)
+    }
+}}
+
+impl std::error::Error for QualityError {}
+
+// Check that two images can be compared pixel by pixel
+pub fn check_compatible(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<(), QualityError> {
+    if original_img.dimensions() != new_img.dimensions() {
+        return Err(QualityError::DimensionMismatch { original : original_img.dimensions(), new : new_img.dimensions() });
+    }
+    if original_img.color() != new_img.color() {
+        return Err(QualityError::ColorTypeMismatch { original : original_img.color(), new : new_img.color() });
+    }
+    if original_img.width() == 0 || original_img.height() == 0 {
+        return Err(QualityError::EmptyImage);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mse, hpsnr, ssim, HvsModel, BorderMode};
+
+    fn luma(width : u32, height : u32) -> image::DynamicImage {
+        image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(width, height, |x, y| image::Luma([(x*16 + y*8) as u8])))
+    }
+
+    fn rgb(width : u32, height : u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(width, height, |x, y| image::Rgb([(x*16) as u8, (y*8) as u8, 128])))
+    }
+
+    // Every metric starts with check_compatible, and fails the same way on mismatched images
+    fn metrics(a : &image::DynamicImage, b : &image::DynamicImage) -> [Result<(), QualityError>; 3] {
+        [mse(a, b).map(|_| ()), hpsnr(a, b, &HvsModel::default(), BorderMode::default()).map(|_| ()), ssim(a, b).map(|_| ())]
+    }
+
+    #[test]
+    fn incompatible_images() {
+        let (gray, color) = (luma(12, 10), rgb(12, 10));
+        for (pair, expected) in [
+            ((&gray, &color), QualityError::ColorTypeMismatch { original : gray.color(), new : color.color() }),
+            ((&gray, &luma(10, 12)), QualityError::DimensionMismatch { original : (12, 10), new : (10, 12) }),
+            ((&color, &rgb(12, 11)), QualityError::DimensionMismatch { original : (12, 10), new : (12, 11) })
+        ] {
+            for result in metrics(pair.0, pair.1) {
+                assert_eq!(result, Err(expected.clone()));
+            }
+        }
+    }
+}
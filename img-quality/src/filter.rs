@@ -0,0 +1,250 @@
+// Normalized 1D Gaussian kernel, of size 2*radius + 1
+pub fn gaussian_kernel(std_dev : f64, radius : usize) -> Vec<f64> {
+    let kernel : Vec<f64> = (0..(2*radius + 1)).map(|i| {
+        let offset = i as f64 - radius as f64;
+        (-(offset*offset) / (2.0*std_dev*std_dev)).exp()
+    }).collect();
+
+    let total : f64 = kernel.iter().sum();
+    kernel.into_iter().map(|k| k / total).collect()
+}
+
+// Filter a flat 2D array with a separable, centered kernel
+// Out of image samples are dropped and the kernel is renormalized over the remaining ones
+pub fn separable_filter(data : &[f64], width : usize, height : usize, kernel : &[f64]) -> Vec<f64> {
+    let radius = (kernel.len() / 2) as isize;
+
+    // Horizontal pass
+    let mut horizontal : Vec<f64> = vec![0.0; data.len()];
+    for y in 0..height {
+        let row = &data[y*width..(y+1)*width];
+        for x in 0..width {
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            for (k, &coef) in kernel.iter().enumerate() {
+                let pos = x as isize + k as isize - radius;
+                if pos < 0 || pos >= width as isize { continue; }
+                sum += coef * row[pos as usize];
+                weight += coef;
+            }
+            horizontal[x + y*width] = sum / weight;
+        }
+    }
+
+    // Vertical pass
+    let mut result : Vec<f64> = vec![0.0; data.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            for (k, &coef) in kernel.iter().enumerate() {
+                let pos = y as isize + k as isize - radius;
+                if pos < 0 || pos >= height as isize { continue; }
+                sum += coef * horizontal[x + pos as usize * width];
+                weight += coef;
+            }
+            result[x + y*width] = sum / weight;
+        }
+    }
+
+    result
+}
+
+// Split a square 2D kernel into its horizontal and vertical 1D factors, if it is separable
+// The kernel is stored row by row, so kernel[m + n*size] == horizontal[m] * vertical[n]
+pub fn separate(kernel : &[f64], size : usize) -> Option<(Vec<f64>, Vec<f64>)> {
+    // Factor through the largest coefficient, which is the best conditioned one
+    let (peak, &peak_val) = kernel.iter().enumerate().max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))?;
+    if peak_val == 0.0 || !peak_val.is_finite() { return None; }
+    let (peak_x, peak_y) = (peak % size, peak / size);
+
+    let horizontal : Vec<f64> = (0..size).map(|m| kernel[m + peak_y*size] / peak_val).collect();
+    let vertical : Vec<f64> = (0..size).map(|n| kernel[peak_x + n*size]).collect();
+
+    // Check that the outer product gives back the kernel
+    let tolerance = peak_val.abs() * 1e-12;
+    for n in 0..size {
+        for m in 0..size {
+            if (horizontal[m] * vertical[n] - kernel[m + n*size]).abs() > tolerance { return None; }
+        }
+    }
+
+    Some((horizontal, vertical))
+}
+
+// How samples outside of the image are handled when filtering
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BorderMode {
+    // Outside samples are 0
+    Zero,
+    // Outside samples take the value of the closest edge pixel : aaa|abcd|ddd
+    Replicate,
+    // Outside samples are mirrored around the edge pixel : dcb|abcd|cba
+    #[default]
+    Mirror,
+    // Only pixels where the whole kernel fits in the image are kept
+    Valid
+}
+
+// Index in 0..len sampled for a position, None when the sample is 0
+fn border_index(pos : isize, len : usize, border : BorderMode) -> Option<usize> {
+    let len = len as isize;
+    if pos >= 0 && pos < len { return Some(pos as usize); }
+    match border {
+        BorderMode::Zero | BorderMode::Valid => None,
+        BorderMode::Replicate => Some(pos.max(0).min(len - 1) as usize),
+        BorderMode::Mirror => {
+            if len == 1 { return Some(0); }
+            let period = 2*(len - 1);
+            let pos = pos.rem_euclid(period);
+            Some(if pos < len { pos } else { period - pos } as usize)
+        }
+    }
+}
+
+// Size of the filtered image along one axis, and offset of its first pixel in the source image
+fn output_range(len : usize, kernel_size : usize, border : BorderMode) -> (usize, isize) {
+    let radius = (kernel_size / 2) as isize;
+    match border {
+        BorderMode::Valid => ((len + 1).saturating_sub(kernel_size), radius),
+        _ => (len, 0)
+    }
+}
+
+// Sampled index of every kernel tap, for every output position along one axis
+fn tap_indices(len : usize, kernel_size : usize, border : BorderMode) -> (Vec<Option<usize>>, usize) {
+    let radius = (kernel_size / 2) as isize;
+    let (out_len, start) = output_range(len, kernel_size, border);
+    let mut taps : Vec<Option<usize>> = Vec::with_capacity(out_len * kernel_size);
+    for out in 0..out_len as isize {
+        for t in 0..kernel_size as isize {
+            taps.push(border_index(out + start + t - radius, len, border));
+        }
+    }
+    (taps, out_len)
+}
+
+// Correlate an interleaved multi-channel image with a centered 2D kernel, stored row by row
+// Returns the filtered image and its dimensions, which only differ from the input for BorderMode::Valid
+pub fn correlate(data : &[f64], width : usize, height : usize, channels : usize, kernel : &[f64], kernel_size : usize, border : BorderMode) -> (Vec<f64>, usize, usize) {
+    let (taps_x, out_width) = tap_indices(width, kernel_size, border);
+    let (taps_y, out_height) = tap_indices(height, kernel_size, border);
+
+    let mut result : Vec<f64> = Vec::with_capacity(out_width * out_height * channels);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            for c in 0..channels {
+                let mut sum = 0.0;
+                for n in 0..kernel_size {
+                    let row = match taps_y[n + y*kernel_size] { Some(row) => row, None => continue };
+                    for m in 0..kernel_size {
+                        if let Some(col) = taps_x[m + x*kernel_size] {
+                            sum += kernel[m + n*kernel_size] * data[(col + row*width)*channels + c];
+                        }
+                    }
+                }
+                result.push(sum);
+            }
+        }
+    }
+
+    (result, out_width, out_height)
+}
+
+// Same as correlate, for a kernel split into horizontal and vertical factors by separate
+pub fn correlate_separable(data : &[f64], width : usize, height : usize, channels : usize, horizontal : &[f64], vertical : &[f64], border : BorderMode) -> (Vec<f64>, usize, usize) {
+    let kernel_size = horizontal.len();
+    let (taps_x, out_width) = tap_indices(width, kernel_size, border);
+    let (taps_y, out_height) = tap_indices(height, kernel_size, border);
+
+    // Horizontal pass, on every row of the source image
+    let mut rows : Vec<f64> = Vec::with_capacity(out_width * height * channels);
+    for y in 0..height {
+        for x in 0..out_width {
+            for c in 0..channels {
+                let mut sum = 0.0;
+                for (m, &coef) in horizontal.iter().enumerate() {
+                    if let Some(col) = taps_x[m + x*kernel_size] {
+                        sum += coef * data[(col + y*width)*channels + c];
+                    }
+                }
+                rows.push(sum);
+            }
+        }
+    }
+
+    // Vertical pass
+    let mut result : Vec<f64> = Vec::with_capacity(out_width * out_height * channels);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            for c in 0..channels {
+                let mut sum = 0.0;
+                for (n, &coef) in vertical.iter().enumerate() {
+                    if let Some(row) = taps_y[n + y*kernel_size] {
+                        sum += coef * rows[(x + row*out_width)*channels + c];
+                    }
+                }
+                result.push(sum);
+            }
+        }
+    }
+
+    (result, out_width, out_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Positions around a ramp of 4 pixels, abcd
+    const POSITIONS : [isize; 10] = [-3, -2, -1, 0, 1, 2, 3, 4, 5, 6];
+
+    fn sampled(border : BorderMode) -> Vec<Option<usize>> {
+        POSITIONS.iter().map(|&pos| border_index(pos, 4, border)).collect()
+    }
+
+    #[test]
+    fn border_indices() {
+        let inside = [Some(0), Some(1), Some(2), Some(3)];
+        let zero : Vec<Option<usize>> = [None; 3].iter().chain(&inside).chain(&[None; 3]).cloned().collect();
+        assert_eq!(sampled(BorderMode::Zero), zero);
+        assert_eq!(sampled(BorderMode::Valid), zero);
+        // aaa|abcd|ddd
+        assert_eq!(sampled(BorderMode::Replicate), [0, 0, 0, 0, 1, 2, 3, 3, 3, 3].iter().map(|&i| Some(i)).collect::<Vec<_>>());
+        // dcb|abcd|cba
+        assert_eq!(sampled(BorderMode::Mirror), [3, 2, 1, 0, 1, 2, 3, 2, 1, 0].iter().map(|&i| Some(i)).collect::<Vec<_>>());
+        // A single pixel mirrors onto itself
+        assert_eq!(border_index(-2, 1, BorderMode::Mirror), Some(0));
+        assert_eq!(border_index(3, 1, BorderMode::Mirror), Some(0));
+    }
+
+    #[test]
+    fn output_ranges() {
+        for border in [BorderMode::Zero, BorderMode::Replicate, BorderMode::Mirror] {
+            assert_eq!(output_range(10, 5, border), (10, 0));
+            assert_eq!(output_range(2, 5, border), (2, 0));
+        }
+        assert_eq!(output_range(10, 5, BorderMode::Valid), (6, 2));
+        assert_eq!(output_range(5, 5, BorderMode::Valid), (1, 2));
+        // Smaller than the kernel, nothing is left
+        assert_eq!(output_range(4, 5, BorderMode::Valid), (0, 2));
+        assert_eq!(output_range(0, 5, BorderMode::Valid), (0, 2));
+    }
+
+    // A kernel picking the left neighbour, on the ramp 1 2 3 4 repeated over 3 rows
+    #[test]
+    fn correlate_edges() {
+        let data : Vec<f64> = (0..3).flat_map(|_| (1..=4).map(|v| v as f64)).collect();
+        let kernel = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let row = |border| {
+            let (result, width, height) = correlate(&data, 4, 3, 1, &kernel, 3, border);
+            (result[width..2*width].to_vec(), width, height)
+        };
+        assert_eq!(row(BorderMode::Zero), (vec![0.0, 1.0, 2.0, 3.0], 4, 3));
+        assert_eq!(row(BorderMode::Replicate), (vec![1.0, 1.0, 2.0, 3.0], 4, 3));
+        assert_eq!(row(BorderMode::Mirror), (vec![2.0, 1.0, 2.0, 3.0], 4, 3));
+        assert_eq!(correlate(&data, 4, 3, 1, &kernel, 3, BorderMode::Valid), (vec![1.0, 2.0], 2, 1));
+        // An image smaller than the kernel has no valid pixel
+        assert_eq!(correlate(&data[..4], 2, 2, 1, &kernel, 3, BorderMode::Valid), (vec![], 0, 0));
+    }
+}
@@ -0,0 +1,88 @@
+use image::GenericImageView;
+use crate::error::{self, QualityError};
+use crate::filter::{self, BorderMode};
+use crate::hvs::HvsModel;
+
+// Autocorrelation of a square kernel stored row by row, of width 2*size - 1 with the zero shift at the center
+fn autocorrelation(kernel : &[f64], size : usize) -> (Vec<f64>, usize) {
+    let auto_size = 2*size - 1;
+    let mut result : Vec<f64> = vec![0.0; auto_size*auto_size];
+    for n in 0..size {
+        for m in 0..size {
+            for q in 0..size {
+                for p in 0..size {
+                    // Shift between the two taps, moved to the center of the result
+                    let dx = p + size - 1 - m;
+                    let dy = q + size - 1 - n;
+                    result[dx + dy*auto_size] += kernel[m + n*size] * kernel[p + q*size];
+                }
+            }
+        }
+    }
+    (result, auto_size)
+}
+
+// Same as autocorrelation, for one factor of a separable kernel
+fn autocorrelation_1d(kernel : &[f64]) -> (Vec<f64>, usize) {
+    let size = kernel.len();
+    let mut result : Vec<f64> = vec![0.0; 2*size - 1];
+    for m in 0..size {
+        for p in 0..size {
+            result[p + size - 1 - m] += kernel[m] * kernel[p];
+        }
+    }
+    (result, 2*size - 1)
+}
+
+// Energy of a signal as seen through the human vision filter, sum of |h * x|² over the whole plane
+// Computed as sum of x(m) x(n) c(m - n), where c is the autocorrelation of the filter and x is 0 outside of the image
+fn perceived_energy(data : &[f64], width : usize, height : usize, channels : usize, model : &HvsModel) -> f64 {
+    let (kernel, size) = model.kernel();
+    let (filtered, _, _) = match filter::separate(&kernel, size) {
+        Some((horizontal, vertical)) => {
+            let (horizontal, _) = autocorrelation_1d(&horizontal);
+            let (vertical, _) = autocorrelation_1d(&vertical);
+            filter::correlate_separable(data, width, height, channels, &horizontal, &vertical, BorderMode::Zero)
+        },
+        None => {
+            let (auto, auto_size) = autocorrelation(&kernel, size);
+            filter::correlate(data, width, height, channels, &auto, auto_size, BorderMode::Zero)
+        }
+    };
+    data.iter().zip(filtered.iter()).map(|(x, c)| x*c).sum()
+}
+
+// Pixels scaled to 0..1, with the image dimensions and number of interleaved channels
+fn normalized(img : &image::DynamicImage) -> (Vec<f64>, usize, usize, usize) {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let pixels : Vec<f64> = img.raw_pixels().into_iter().map(|p| p as f64 / 255.0).collect();
+    let channels = pixels.len() / (width * height);
+    (pixels, width, height, channels)
+}
+
+// Compute the perceived error of a halftone, the cost minimized by Direct Binary Search
+// From Lieberman & Allebach, "A dual interpretation for direct binary search and its implications for tone reproduction and texture quality"
+// Pixels are scaled to 0..1, and the cost is averaged over the pixels so that images of different sizes compare
+pub fn perceived_error(original_img : &image::DynamicImage, halftone_img : &image::DynamicImage, model : &HvsModel) -> Result<f64, QualityError> {
+    error::check_compatible(original_img, halftone_img)?;
+    model.validate()?;
+    let (original, width, height, channels) = normalized(original_img);
+    let (halftone, _, _, _) = normalized(halftone_img);
+
+    let error : Vec<f64> = original.iter().zip(halftone.iter()).map(|(o, h)| o - h).collect();
+    Ok(perceived_energy(&error, width, height, channels, model) / error.len() as f64)
+}
+
+// Compute the weighted signal-to-noise ratio, in dB, of a halftone
+// Signal and error are both weighted by the human vision model, from Mitsa & Varkur, "Evaluation of contrast sensitivity functions for the formulation of quality measures incorporated in halftoning algorithms"
+pub fn wsnr(original_img : &image::DynamicImage, halftone_img : &image::DynamicImage, model : &HvsModel) -> Result<f64, QualityError> {
+    error::check_compatible(original_img, halftone_img)?;
+    model.validate()?;
+    let (original, width, height, channels) = normalized(original_img);
+    let (halftone, _, _, _) = normalized(halftone_img);
+
+    let error : Vec<f64> = original.iter().zip(halftone.iter()).map(|(o, h)| o - h).collect();
+    let signal_energy = perceived_energy(&original, width, height, channels, model);
+    let error_energy = perceived_energy(&error, width, height, channels, model);
+    Ok(10.0 * (signal_energy / error_energy).log10())
+}
@@ -0,0 +1,149 @@
+use image::GenericImageView;
+use crate::error::QualityError;
+use crate::filter::BorderMode;
+use crate::hvs::HvsModel;
+
+// Viridis colormap stops, from low to high values
+static COLORMAP : [[f64; 3]; 5] = [
+    [68.0, 1.0, 84.0],
+    [59.0, 82.0, 139.0],
+    [33.0, 145.0, 140.0],
+    [94.0, 201.0, 98.0],
+    [253.0, 231.0, 37.0]
+];
+
+// Legend layout, in pixels
+static LEGEND_HEIGHT : usize = 30;
+static LEGEND_MARGIN : usize = 4;
+static BAR_HEIGHT : usize = 10;
+static FONT_SCALE : usize = 2;
+static LEGEND_BACKGROUND : [u8; 3] = [32, 32, 32];
+static LEGEND_TEXT : [u8; 3] = [255, 255, 255];
+
+// 3x5 bitmap font for the legend labels, one row of 3 bits per line from the top
+static FONT : [(char, [u8; 5]); 12] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000])
+];
+
+// Color of a value between 0 and 1
+fn colormap(t : f64) -> [u8; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let position = t * (COLORMAP.len() - 1) as f64;
+    let low = (position.floor() as usize).min(COLORMAP.len() - 2);
+    let frac = position - low as f64;
+    let mut color = [0; 3];
+    for (c, value) in color.iter_mut().enumerate() {
+        *value = (COLORMAP[low][c] * (1.0 - frac) + COLORMAP[low + 1][c] * frac).round() as u8;
+    }
+    color
+}
+
+// Short label for a legend value, without exponent notation
+fn label(value : f64) -> String {
+    match value.abs() {
+        v if v < 10.0 => format!("{:.3}", value),
+        v if v < 100.0 => format!("{:.2}", value),
+        v if v < 1000.0 => format!("{:.1}", value),
+        _ => format!("{:.0}", value)
+    }
+}
+
+fn label_width(text : &str) -> usize {
+    text.len() * 4 * FONT_SCALE
+}
+
+// Draw a label with its top left corner at (x, y), clipped to the image
+fn draw_label(img : &mut image::RgbImage, text : &str, x : usize, y : usize) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph = match FONT.iter().find(|(g, _)| *g == c) { Some((_, glyph)) => glyph, None => continue };
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 { continue; }
+                for dy in 0..FONT_SCALE {
+                    for dx in 0..FONT_SCALE {
+                        let px = x + (i*4 + col)*FONT_SCALE + dx;
+                        let py = y + row*FONT_SCALE + dy;
+                        if px < img.width() as usize && py < img.height() as usize {
+                            img.put_pixel(px as u32, py as u32, image::Rgb(LEGEND_TEXT));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Colorize a map of values stored row by row, with a color bar legend from min to max under it
+pub fn colorize(values : &[f64], width : usize, height : usize, min : f64, max : f64) -> image::DynamicImage {
+    let range = if max > min { max - min } else { 1.0 };
+    let mut img = image::RgbImage::from_pixel(width as u32, (height + LEGEND_HEIGHT) as u32, image::Rgb(LEGEND_BACKGROUND));
+
+    // Map
+    for (i, &value) in values.iter().enumerate() {
+        img.put_pixel((i % width) as u32, (i / width) as u32, image::Rgb(colormap((value - min) / range)));
+    }
+
+    // Color bar, across the whole width
+    let bar_top = height + LEGEND_MARGIN;
+    for x in 0..width {
+        let color = colormap(x as f64 / (width.max(2) - 1) as f64);
+        for y in bar_top..bar_top + BAR_HEIGHT {
+            img.put_pixel(x as u32, y as u32, image::Rgb(color));
+        }
+    }
+
+    // Labels for both ends and the middle of the bar
+    let label_top = bar_top + BAR_HEIGHT + LEGEND_MARGIN / 2;
+    let (low, mid, high) = (label(min), label((min + max) / 2.0), label(max));
+    draw_label(&mut img, &low, 0, label_top);
+    draw_label(&mut img, &mid, (width / 2).saturating_sub(label_width(&mid) / 2), label_top);
+    draw_label(&mut img, &high, width.saturating_sub(label_width(&high)), label_top);
+
+    image::DynamicImage::ImageRgb8(img)
+}
+
+// Average of the absolute values of each pixel over its interleaved channels
+fn per_pixel(values : &[f64], channels : usize) -> Vec<f64> {
+    values.chunks(channels).map(|pixel| pixel.iter().map(|v| v.abs()).sum::<f64>() / channels as f64).collect()
+}
+
+fn max_or_one(values : &[f64]) -> f64 {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    if max > 0.0 { max } else { 1.0 }
+}
+
+// Heatmap of the absolute error between two images, in gray levels
+pub fn absolute_error_heatmap(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<image::DynamicImage, QualityError> {
+    let (diff, width, height, channels) = crate::difference(original_img, new_img)?;
+    let errors = per_pixel(&diff, channels);
+    Ok(colorize(&errors, width, height, 0.0, max_or_one(&errors)))
+}
+
+// Heatmap of the absolute error between two images once filtered by the human vision model
+// With BorderMode::Valid the heatmap is smaller than the images
+pub fn hvs_error_heatmap(original_img : &image::DynamicImage, new_img : &image::DynamicImage, model : &HvsModel, border : BorderMode) -> Result<image::DynamicImage, QualityError> {
+    let (filtered, width, height, channels) = crate::filtered_difference(original_img, new_img, model, border)?;
+    if filtered.is_empty() {
+        return Err(QualityError::ImageTooSmall { dimensions : (original_img.width(), original_img.height()), minimum : (model.size() as u32, model.size() as u32) });
+    }
+    let errors = per_pixel(&filtered, channels);
+    Ok(colorize(&errors, width, height, 0.0, max_or_one(&errors)))
+}
+
+// Heatmap of the local SSIM between two images, from the lowest value up to 1
+pub fn ssim_heatmap(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<image::DynamicImage, QualityError> {
+    let (_, map) = crate::ssim_map(original_img, new_img)?;
+    let min = map.values.iter().cloned().fold(1.0, f64::min);
+    Ok(colorize(&map.values, map.width, map.height, min, 1.0))
+}
@@ -0,0 +1,196 @@
+use std::f64::consts::PI;
+use rustfft::{FftPlanner, num_complex::Complex};
+use crate::error::QualityError;
+
+// Frequency grid used to sample contrast sensitivity functions before going back to the spatial domain
+static CSF_GRID_SIZE : usize = 128;
+
+// Average luminance in cd/m², for the Näsänen model
+static NASANEN_LUMINANCE : f64 = 11.0;
+
+// Human visual system model used to weight the error
+// dpi is the printer or screen resolution, viewing_distance is in inches
+#[derive(Debug, Clone, PartialEq)]
+pub enum HvsModel {
+    // Gaussian low-pass filter, std_dev in pixels
+    Gaussian { std_dev : f64, size : usize },
+    // Exponential contrast sensitivity, from Näsänen, "Visibility of halftone dot textures"
+    Nasanen { dpi : f64, viewing_distance : f64, size : usize },
+    // Contrast sensitivity from Mannos & Sakrison, "The effects of a visual fidelity criterion on the encoding of images"
+    // Flattened below its peak, as done by Sullivan et al., so that the mean gray level is not discarded
+    MannosSakrison { dpi : f64, viewing_distance : f64, size : usize }
+}
+
+impl Default for HvsModel {
+    fn default() -> HvsModel {
+        HvsModel::Gaussian { std_dev : 1.3, size : 9 }
+    }
+}
+
+impl HvsModel {
+    pub fn size(&self) -> usize {
+        match *self {
+            HvsModel::Gaussian { size, .. } | HvsModel::Nasanen { size, .. } | HvsModel::MannosSakrison { size, .. } => size
+        }
+    }
+
+    // Check that the parameters give a usable filter, every metric taking a model starts with it
+    // The size must be odd for the filter to have a center pixel
+    pub fn validate(&self) -> Result<(), QualityError> {
+        let size = self.size();
+        if size.is_multiple_of(2) { return Err(QualityError::InvalidParameter { name : "filter size", value : size as f64 }); }
+        match *self {
+            HvsModel::Gaussian { std_dev, .. } => positive("standard deviation", std_dev),
+            HvsModel::Nasanen { dpi, viewing_distance, .. } | HvsModel::MannosSakrison { dpi, viewing_distance, .. } =>
+                check_viewing_conditions(dpi, viewing_distance)
+        }
+    }
+
+    // Spatial filter, stored row by row, and its width
+    pub fn kernel(&self) -> (Vec<f64>, usize) {
+        match *self {
+            HvsModel::Gaussian { std_dev, size } => (gaussian_kernel(std_dev, size), size),
+            HvsModel::Nasanen { dpi, viewing_distance, size } =>
+                (csf_kernel(nasanen, pixels_per_degree(dpi, viewing_distance), size), size),
+            HvsModel::MannosSakrison { dpi, viewing_distance, size } =>
+                (csf_kernel(mannos_sakrison, pixels_per_degree(dpi, viewing_distance), size), size)
+        }
+    }
+}
+
+fn gaussian_kernel(std_dev : f64, hvf_size : usize) -> Vec<f64> {
+    let length = hvf_size*hvf_size;
+    let mut filter : Vec<f64> = vec![];
+    for i in 0..length {
+        // Position in the array
+        let x : usize = i % hvf_size;
+        let y : usize = (i-x) / hvf_size;
+
+        // Position in the array centered around zero
+        let x_off : i32 = x as i32 - (hvf_size / 2) as i32;
+        let y_off : i32 = y as i32 - (hvf_size / 2) as i32;
+
+        // Gauss function
+        let distance_sq : usize = x_off.pow(2) as usize + y_off.pow(2) as usize;
+        let exp_part : f64 = -(distance_sq as f64)/(2.0*std_dev*std_dev);
+        let fact_part : f64 = 1.0/(2.0*std_dev);
+
+        // Save
+        filter.push(fact_part * exp_part.exp());
+    }
+
+    filter
+}
+
+fn positive(name : &'static str, value : f64) -> Result<(), QualityError> {
+    if value > 0.0 && value.is_finite() { Ok(()) } else { Err(QualityError::InvalidParameter { name, value }) }
+}
+
+// Resolution and viewing distance must be positive for pixels_per_degree to mean anything
+pub(crate) fn check_viewing_conditions(dpi : f64, viewing_distance : f64) -> Result<(), QualityError> {
+    positive("resolution", dpi)?;
+    positive("viewing distance", viewing_distance)
+}
+
+// Number of pixels seen in one degree of visual angle
+pub(crate) fn pixels_per_degree(dpi : f64, viewing_distance : f64) -> f64 {
+    2.0 * viewing_distance * (PI / 360.0).tan() * dpi
+}
+
+// Sensitivity at a radial frequency in cycles per degree
+fn nasanen(frequency : f64) -> f64 {
+    let (a, b, c, d) = (131.6, 0.3188, 0.525, 3.91);
+    a * NASANEN_LUMINANCE.powf(b) * (-frequency / (c * NASANEN_LUMINANCE.ln() + d)).exp()
+}
+
+fn mannos_sakrison(frequency : f64) -> f64 {
+    let peak_frequency = 8.0;
+    let frequency = frequency.max(peak_frequency);
+    2.6 * (0.0192 + 0.114*frequency) * (-(0.114*frequency).powf(1.1)).exp()
+}
+
+// Centered spatial filter from a radial contrast sensitivity function, with unit DC gain
+// The CSF is sampled up to the Nyquist frequency and brought back with an inverse FFT, cropped to the taps we keep
+fn csf_kernel(csf : fn(f64) -> f64, pixels_per_degree : f64, size : usize) -> Vec<f64> {
+    let grid = CSF_GRID_SIZE.max(2*size);
+    let half = grid as isize / 2;
+
+    // Frequency response, in cycles per pixel converted to cycles per degree, with the DC at 0
+    let mut response : Vec<Complex<f64>> = Vec::with_capacity(grid*grid);
+    for v in 0..grid as isize {
+        for u in 0..grid as isize {
+            let (fu, fv) = (if u >= half { u - grid as isize } else { u }, if v >= half { v - grid as isize } else { v });
+            let frequency = ((fu*fu + fv*fv) as f64).sqrt() / grid as f64 * pixels_per_degree;
+            response.push(Complex::new(csf(frequency), 0.0));
+        }
+    }
+
+    // Inverse FFT of the rows, then of the columns through a transposition, so the result is indexed by [y + x*grid]
+    // The response is even so the result is real
+    let fft = FftPlanner::new().plan_fft_inverse(grid);
+    fft.process(&mut response);
+    let mut spatial : Vec<Complex<f64>> = (0..grid*grid).map(|i| response[(i / grid) + (i % grid)*grid]).collect();
+    fft.process(&mut spatial);
+
+    let radius = (size / 2) as isize;
+    let wrap = |offset : isize| offset.rem_euclid(grid as isize) as usize;
+    let mut kernel : Vec<f64> = Vec::with_capacity(size*size);
+    for y in 0..size as isize {
+        for x in 0..size as isize {
+            kernel.push(spatial[wrap(y - radius) + wrap(x - radius)*grid].re);
+        }
+    }
+
+    let total : f64 = kernel.iter().sum();
+    kernel.into_iter().map(|k| k / total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_parameters() {
+        let invalid = [
+            HvsModel::Gaussian { std_dev : 0.0, size : 9 },
+            HvsModel::Gaussian { std_dev : f64::NAN, size : 9 },
+            HvsModel::Gaussian { std_dev : 1.3, size : 0 },
+            HvsModel::Gaussian { std_dev : 1.0, size : 8 },
+            HvsModel::Nasanen { dpi : 300.0, viewing_distance : 10.0, size : 10 },
+            HvsModel::Nasanen { dpi : 0.0, viewing_distance : 10.0, size : 9 },
+            HvsModel::MannosSakrison { dpi : 300.0, viewing_distance : -1.0, size : 9 }
+        ];
+        for model in invalid.iter() {
+            assert!(matches!(model.validate(), Err(QualityError::InvalidParameter { .. })), "{:?}", model);
+        }
+        assert_eq!(HvsModel::Gaussian { std_dev : 1.0, size : 8 }.validate(), Err(QualityError::InvalidParameter { name : "filter size", value : 8.0 }));
+        assert_eq!(HvsModel::default().validate(), Ok(()));
+    }
+
+    // The inverse FFT gives the same taps as summing the cosines of the inverse DFT directly
+    #[test]
+    fn csf_kernel_matches_dft() {
+        let (pixels_per_degree, size) = (pixels_per_degree(150.0, 12.0), 7);
+        let grid = CSF_GRID_SIZE;
+        let half = grid as isize / 2;
+        let radius = (size / 2) as isize;
+        let mut direct : Vec<f64> = vec![];
+        for y in 0..size as isize {
+            for x in 0..size as isize {
+                let mut value = 0.0;
+                for v in -half..half {
+                    for u in -half..half {
+                        let frequency = ((u*u + v*v) as f64).sqrt() / grid as f64 * pixels_per_degree;
+                        value += mannos_sakrison(frequency) * (2.0 * PI * (u*(x - radius) + v*(y - radius)) as f64 / grid as f64).cos();
+                    }
+                }
+                direct.push(value);
+            }
+        }
+        let total : f64 = direct.iter().sum();
+
+        for (fast, slow) in csf_kernel(mannos_sakrison, pixels_per_degree, size).into_iter().zip(direct) {
+            assert!((fast - slow / total).abs() < 1e-12, "{} vs {}", fast, slow / total);
+        }
+    }
+}
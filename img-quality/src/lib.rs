@@ -1,82 +1,83 @@
 use image::GenericImageView;
 
-// Get value from flat 2D array, or 0 if out of bounds
-fn get_val<T>(arr: &Vec<T>, width: usize, x: usize, y: usize) -> T where T : Default + Copy {
-    if x + (y*width) >= arr.len() { return T::default(); }
-    return arr[x + (y*width)];
-}
+mod color;
+mod error;
+mod filter;
+mod halftone;
+mod heatmap;
+mod hvs;
+mod spectrum;
+mod ssim;
 
-// Human vision filter
-pub fn get_hvf() -> (Vec<f64>, usize) {
-    let std_dev : f64 = 1.3;   // Human vision model
-    let hvf_size : usize = 9;    // Arbitrary
-    let length = hvf_size*hvf_size;
-    let mut filter : Vec<f64> = vec![];
-    for i in 0..length {
-        // Position in the array
-        let x : usize = i % hvf_size;
-        let y : usize = (i-x) / hvf_size;
-        // println!("pos : ({:?}, {:?})", x, y);
-
-        // Position in the array centered around zero
-        let x_off : i32 = x as i32 - ((hvf_size as f64/2.0) + 1.0).floor() as i32;
-        let y_off : i32 = y as i32 -((hvf_size as f64/2.0) + 1.0).floor() as i32;
-        // println!("pos_off : ({:?}, {:?})", x_off, y_off);
-
-        // Gauss function
-        let distance_sq : usize = x_off.pow(2) as usize + y_off.pow(2) as usize;
-        let exp_part : f64 = -(distance_sq as f64)/(2.0*std_dev*std_dev);
-        let fact_part : f64 = 1.0/(2.0*std_dev);
-        // println!("vals : {:?}, {:?}, {:?}, {:?} ", distance_sq, exp_part, fact_part, fact_part*exp_part.exp());
-
-        // Save
-        filter.push(fact_part * exp_part.exp());
-    }
+pub use color::{delta_e_2000, ciede2000, ciede2000_map, ciede2000_percentile, s_cielab};
+pub use error::QualityError;
+pub use filter::BorderMode;
+pub use halftone::{perceived_error, wsnr};
+pub use heatmap::{colorize, absolute_error_heatmap, hvs_error_heatmap, ssim_heatmap};
+pub use hvs::HvsModel;
+pub use spectrum::{Spectrum, spectrum};
+pub use ssim::{SsimMap, ssim, ssim_map, ms_ssim, ms_ssim_map};
 
-    return (filter, hvf_size);
+// Human vision filter of the default model
+pub fn get_hvf() -> (Vec<f64>, usize) {
+    HvsModel::default().kernel()
 }
 
-// Compute human-filtered peak-to-peak signal-to-noise ratio
-pub fn hpsnr(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<f64, String> {
-    let (filter, hvf_size) = get_hvf();
-    
-    // Dimensions check
-    if original_img.dimensions() != new_img.dimensions() { return Err(format!("Size doesn't match : {:?} vs {:?}", original_img.dimensions(), new_img.dimensions())); }
+// Difference between two images, with its dimensions and number of interleaved channels
+pub(crate) fn difference(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<(Vec<f64>, usize, usize, usize), QualityError> {
+    error::check_compatible(original_img, new_img)?;
     let img_width = original_img.dimensions().0 as usize;
     let img_height = original_img.dimensions().1 as usize;
-    let original = original_img.raw_pixels();
-    let new = new_img.raw_pixels();
-    
-    // Double loop for the denominator
-    let mut doublesum = 0.0;
-    for i in 0..img_width {
-        for j in 0..img_height {
-
-            let mut sum : f64 = 0.0;
-            for m in 0..hvf_size {
-                for n in 0..hvf_size {
-                    sum += get_val(&filter, hvf_size, m, n) * (get_val(&original, img_width, i+m, j+n) as f64 - get_val(&new, img_width, i+m, j+n) as f64);
-                }
-            }
-            doublesum += sum.powf(2.0);
+    let diff : Vec<f64> = original_img.raw_pixels().into_iter().zip(new_img.raw_pixels())
+        .map(|(o, n)| o as f64 - n as f64).collect();
+    let channels = diff.len() / (img_width * img_height);
+    Ok((diff, img_width, img_height, channels))
+}
 
-        }
-    }
-    
-    // Final calculation
-    let hpsnr = 10.0 * ((img_width as f64 * img_height as f64 * 255.0 * 255.0) / doublesum).log(10.0);
-    return Ok(hpsnr);
+// Peak signal-to-noise ratio of the filtered error, empty when the image is smaller than the filter
+fn filtered_psnr(filtered : &[f64], original_img : &image::DynamicImage, hvf_size : usize) -> Result<f64, QualityError> {
+    if filtered.is_empty() { return Err(QualityError::ImageTooSmall { dimensions : original_img.dimensions(), minimum : (hvf_size as u32, hvf_size as u32) }); }
+    let doublesum : f64 = filtered.iter().map(|v| v*v).sum();
+    Ok(10.0 * ((filtered.len() as f64 * 255.0 * 255.0) / doublesum).log(10.0))
+}
+
+// Difference between two images filtered by the human vision model, with its dimensions and number of interleaved channels
+// Uses two 1D passes when the human vision filter is separable, which the Gaussian one is
+pub(crate) fn filtered_difference(original_img : &image::DynamicImage, new_img : &image::DynamicImage, model : &HvsModel, border : BorderMode) -> Result<(Vec<f64>, usize, usize, usize), QualityError> {
+    model.validate()?;
+    let (filter, hvf_size) = model.kernel();
+    let (diff, img_width, img_height, channels) = difference(original_img, new_img)?;
+
+    let (filtered, width, height) = match filter::separate(&filter, hvf_size) {
+        Some((horizontal, vertical)) => filter::correlate_separable(&diff, img_width, img_height, channels, &horizontal, &vertical, border),
+        None => filter::correlate(&diff, img_width, img_height, channels, &filter, hvf_size, border)
+    };
+    Ok((filtered, width, height, channels))
+}
+
+// Compute human-filtered peak-to-peak signal-to-noise ratio
+pub fn hpsnr(original_img : &image::DynamicImage, new_img : &image::DynamicImage, model : &HvsModel, border : BorderMode) -> Result<f64, QualityError> {
+    let (filtered, _, _, _) = filtered_difference(original_img, new_img, model, border)?;
+    filtered_psnr(&filtered, original_img, model.size())
+}
+
+// Compute human-filtered peak-to-peak signal-to-noise ratio with a direct 2D convolution
+// Reference implementation for hpsnr, which gives the same numbers faster
+pub fn hpsnr_direct(original_img : &image::DynamicImage, new_img : &image::DynamicImage, model : &HvsModel, border : BorderMode) -> Result<f64, QualityError> {
+    model.validate()?;
+    let (filter, hvf_size) = model.kernel();
+    let (diff, img_width, img_height, channels) = difference(original_img, new_img)?;
+
+    let (filtered, _, _) = filter::correlate(&diff, img_width, img_height, channels, &filter, hvf_size, border);
+    filtered_psnr(&filtered, original_img, hvf_size)
 }
 
 // Compute mean square error
-pub fn mse(img1 : &image::DynamicImage, img2 : &image::DynamicImage) -> Result<f32, String> {
+pub fn mse(img1 : &image::DynamicImage, img2 : &image::DynamicImage) -> Result<f32, QualityError> {
+    error::check_compatible(img1, img2)?;
     let pix1 = img1.raw_pixels();
     let pix2 = img2.raw_pixels();
 
-    if pix1.len() != pix2.len() {
-        return Err("Size doesn't match".to_string());
-    }
-
     let mut sum : f32 = 0.0;
     for pixels in pix1.iter().zip(pix2.iter()) {
         let (p1, p2) = pixels;
@@ -85,5 +86,41 @@ pub fn mse(img1 : &image::DynamicImage, img2 : &image::DynamicImage) -> Result<f
         sum += (pix1 - pix2).powf(2.0);
     }
 
-    return Ok(sum / (pix1.len() as f32));
+    Ok(sum / (pix1.len() as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Non-square, with every channel different, so that a mix-up of width, height or channels shows
+    fn test_image(seed : u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(23, 17, |x, y| {
+            let v = (x * 37 + y * 91 + seed * 53) ^ (x * y);
+            image::Rgb([(v % 256) as u8, (v * 7 % 256) as u8, ((x + 3 * y + seed) * 11 % 256) as u8])
+        }))
+    }
+
+    #[test]
+    fn separable_hpsnr_matches_direct() {
+        let (original, new) = (test_image(0), test_image(1));
+        let models = [
+            HvsModel::default(),
+            HvsModel::Gaussian { std_dev : 2.0, size : 7 },
+            HvsModel::Nasanen { dpi : 300.0, viewing_distance : 10.0, size : 9 },
+            HvsModel::MannosSakrison { dpi : 150.0, viewing_distance : 12.0, size : 5 }
+        ];
+        for model in models.iter() {
+            // The Gaussian filters must take the fast path, or the test compares the direct path with itself
+            if let HvsModel::Gaussian { .. } = model {
+                let (kernel, size) = model.kernel();
+                assert!(filter::separate(&kernel, size).is_some());
+            }
+            for &border in [BorderMode::Zero, BorderMode::Replicate, BorderMode::Mirror, BorderMode::Valid].iter() {
+                let fast = hpsnr(&original, &new, model, border).unwrap();
+                let direct = hpsnr_direct(&original, &new, model, border).unwrap();
+                assert!((fast - direct).abs() <= 1e-12 * direct.abs(), "{:?} {:?} : {} vs {}", model, border, fast, direct);
+            }
+        }
+    }
 }
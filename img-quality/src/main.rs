@@ -0,0 +1,170 @@
+use structopt::StructOpt;
+use std::fs;
+use std::io::{self, Write};
+use img_quality::{HvsModel, BorderMode, QualityError};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "img-quality", about = "Compare images against a reference with every available quality metric")]
+struct Opt {
+    /// Original image the others are compared with
+    #[structopt(name = "REFERENCE")]
+    reference: String,
+    /// Images to compare with the reference
+    #[structopt(name = "CANDIDATES", required = true)]
+    candidates: Vec<String>,
+    /// Report format
+    #[structopt(short, long, default_value = "text", possible_values = &["text", "csv", "json"])]
+    format: String,
+    /// Write the report to a file instead of stdout
+    #[structopt(short, long)]
+    output: Option<String>,
+    /// Printer or screen resolution, for the S-CIELAB filters
+    #[structopt(long, default_value = "300")]
+    dpi: f64,
+    /// Viewing distance in inches, for the S-CIELAB filters
+    #[structopt(long, default_value = "10")]
+    viewing_distance: f64
+}
+
+static METRICS : [&str; 9] = ["mse", "hpsnr", "perceived_error", "wsnr", "ssim", "ms_ssim", "ciede2000", "ciede2000_p95", "s_cielab"];
+
+// Metrics of one candidate, None where the metric couldn't be computed
+struct Row {
+    file : String,
+    values : Vec<Option<f64>>,
+    error : Option<String>
+}
+
+fn compute_row(reference : &image::DynamicImage, file : &str, opt : &Opt) -> Row {
+    let candidate = match image::open(file) {
+        Ok(img) => img,
+        Err(e) => return Row { file : file.to_string(), values : vec![None; METRICS.len()], error : Some(e.to_string()) }
+    };
+
+    let model = HvsModel::default();
+    let border = BorderMode::default();
+    let results : Vec<Result<f64, QualityError>> = vec![
+        img_quality::mse(reference, &candidate).map(|v| v as f64),
+        img_quality::hpsnr(reference, &candidate, &model, border),
+        img_quality::perceived_error(reference, &candidate, &model),
+        img_quality::wsnr(reference, &candidate, &model),
+        img_quality::ssim(reference, &candidate),
+        img_quality::ms_ssim(reference, &candidate),
+        img_quality::ciede2000(reference, &candidate),
+        img_quality::ciede2000_percentile(reference, &candidate, 95.0),
+        img_quality::s_cielab(reference, &candidate, opt.dpi, opt.viewing_distance)
+    ];
+
+    // Keep the first failure, which is usually shared by every metric
+    let error = results.iter().find_map(|r| r.as_ref().err()).map(|e| e.to_string());
+    Row { file : file.to_string(), values : results.into_iter().map(|r| r.ok()).collect(), error }
+}
+
+fn write_text<W : Write>(out : &mut W, rows : &[Row]) -> io::Result<()> {
+    let file_width = rows.iter().map(|r| r.file.len()).max().unwrap_or(0).max("file".len());
+    write!(out, "{:<width$}", "file", width = file_width)?;
+    for metric in METRICS.iter() { write!(out, "  {:>width$}", metric, width = metric.len().max(13))?; }
+    writeln!(out)?;
+
+    for row in rows {
+        write!(out, "{:<width$}", row.file, width = file_width)?;
+        for (metric, value) in METRICS.iter().zip(row.values.iter()) {
+            let width = metric.len().max(13);
+            match value {
+                Some(v) => write!(out, "  {:>width$.6}", v, width = width)?,
+                None => write!(out, "  {:>width$}", "-", width = width)?
+            }
+        }
+        if let Some(error) = &row.error { write!(out, "  ({})", error)?; }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn csv_field(text : &str) -> String {
+    if text.contains([',', '"', '\n']) { format!("\"{}\"", text.replace('"', "\"\"")) } else { text.to_string() }
+}
+
+fn write_csv<W : Write>(out : &mut W, rows : &[Row]) -> io::Result<()> {
+    writeln!(out, "file,{},error", METRICS.join(","))?;
+    for row in rows {
+        let values : Vec<String> = row.values.iter().map(|v| v.map(|v| v.to_string()).unwrap_or_default()).collect();
+        writeln!(out, "{},{},{}", csv_field(&row.file), values.join(","), csv_field(row.error.as_deref().unwrap_or("")))?;
+    }
+    Ok(())
+}
+
+fn json_string(text : &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    result
+}
+
+// JSON has no infinity or NaN, so those are written as null too
+fn json_number(value : Option<f64>) -> String {
+    match value {
+        Some(v) if v.is_finite() => v.to_string(),
+        _ => "null".to_string()
+    }
+}
+
+fn write_json<W : Write>(out : &mut W, reference : &str, rows : &[Row]) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"reference\": {},", json_string(reference))?;
+    writeln!(out, "  \"results\": [")?;
+    for (i, row) in rows.iter().enumerate() {
+        let mut fields : Vec<String> = vec![format!("\"file\": {}", json_string(&row.file))];
+        for (metric, value) in METRICS.iter().zip(row.values.iter()) {
+            fields.push(format!("\"{}\": {}", metric, json_number(*value)));
+        }
+        fields.push(format!("\"error\": {}", row.error.as_ref().map(|e| json_string(e)).unwrap_or_else(|| "null".to_string())));
+        writeln!(out, "    {{ {} }}{}", fields.join(", "), if i + 1 < rows.len() { "," } else { "" })?;
+    }
+    writeln!(out, "  ]")?;
+    writeln!(out, "}}")
+}
+
+fn main() {
+    // Parse arguments
+    let opt = Opt::from_args();
+
+    let reference = match image::open(&opt.reference) {
+        Ok(img) => img,
+        Err(e) => {
+            eprintln!("Can't read reference {} : {}", opt.reference, e);
+            std::process::exit(1);
+        }
+    };
+
+    let rows : Vec<Row> = opt.candidates.iter().map(|file| compute_row(&reference, file, &opt)).collect();
+
+    let mut out : Box<dyn Write> = match &opt.output {
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Can't create {} : {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout())
+    };
+    let written = match &opt.format[..] {
+        "csv" => write_csv(&mut out, &rows),
+        "json" => write_json(&mut out, &opt.reference, &rows),
+        _ => write_text(&mut out, &rows)
+    };
+    // The buffered writer would drop a late error, so flush it here
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        eprintln!("Can't write the report : {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,182 @@
+use std::io;
+use image::GenericImageView;
+use rustfft::{FftPlanner, num_complex::Complex};
+use crate::error::QualityError;
+
+// Spectral statistics of a halftone pattern, from Ulichney, "Dithering with blue noise"
+// Frequencies are radial, in cycles per pixel, one annulus per frequency
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    // Center frequency of each annulus
+    pub frequencies : Vec<f64>,
+    // Radially averaged power spectrum density, normalized by the variance g(1-g) so white noise is flat at 1
+    pub rapsd : Vec<f64>,
+    // Variance of the power over each annulus, relative to its squared mean. 0 for a perfectly isotropic pattern
+    pub anisotropy : Vec<f64>,
+    // Fraction of white pixels
+    pub gray_level : f64,
+    // Expected peak frequency of blue noise at this gray level
+    pub principal_frequency : f64
+}
+
+impl Spectrum {
+    // Anisotropy of each annulus in decibels, as usually plotted. Annuli with no anisotropy give -inf
+    pub fn anisotropy_db(&self) -> Vec<f64> {
+        self.anisotropy.iter().map(|a| 10.0 * a.log10()).collect()
+    }
+
+    // Mean anisotropy in decibels over every annulus with power, as a single measure for the whole pattern
+    // Blue noise sits around -10dB, while directional artifacts such as worms raise it
+    // Isotropic annuli count as 0, and the mean is -inf when all of them are, as for anisotropy_db
+    pub fn mean_anisotropy_db(&self) -> f64 {
+        let (sum, count) = self.anisotropy.iter().zip(self.rapsd.iter())
+            .filter(|&(_, &p)| p > 0.0)
+            .fold((0.0, 0), |(sum, count), (a, _)| (sum + a, count + 1));
+        if count == 0 { return f64::NEG_INFINITY; }
+        10.0 * (sum / count as f64).log10()
+    }
+
+    // Frequency where the RAPSD is the highest
+    pub fn peak_frequency(&self) -> f64 {
+        let (peak, _) = self.rapsd.iter().enumerate().fold((0, 0.0), |best, (i, &p)| if p > best.1 { (i, p) } else { best });
+        self.frequencies[peak]
+    }
+
+    // Write the curves as CSV, one annulus per line
+    pub fn write_csv<W : io::Write>(&self, mut writer : W) -> io::Result<()> {
+        writeln!(writer, "frequency,rapsd,anisotropy,anisotropy_db")?;
+        for (i, &frequency) in self.frequencies.iter().enumerate() {
+            writeln!(writer, "{},{},{},{}", frequency, self.rapsd[i], self.anisotropy[i], 10.0 * self.anisotropy[i].log10())?;
+        }
+        Ok(())
+    }
+}
+
+// Power spectrum of a square segment, indexed by [v + u*size] with the DC at 0
+// u is the horizontal frequency and v the vertical one, the output of the column pass stays transposed
+fn periodogram(planner : &mut FftPlanner<f64>, segment : &[f64], size : usize) -> Vec<f64> {
+    let fft = planner.plan_fft_forward(size);
+
+    // Rows, then columns through a transposition
+    let mut rows : Vec<Complex<f64>> = segment.iter().map(|&v| Complex::new(v, 0.0)).collect();
+    fft.process(&mut rows);
+    let mut columns : Vec<Complex<f64>> = (0..size*size).map(|i| rows[(i / size) + (i % size)*size]).collect();
+    fft.process(&mut columns);
+
+    columns.iter().map(|c| c.norm_sqr() / (size*size) as f64).collect()
+}
+
+// Compute the radially averaged power spectrum and anisotropy of a halftone
+// Pixels are thresholded to black and white, then the periodograms of square segments of segment_size pixels are averaged
+pub fn spectrum(img : &image::DynamicImage, segment_size : usize) -> Result<Spectrum, QualityError> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    if width == 0 || height == 0 { return Err(QualityError::EmptyImage); }
+    if segment_size == 0 { return Err(QualityError::InvalidParameter { name : "segment size", value : 0.0 }); }
+    if width < segment_size || height < segment_size {
+        return Err(QualityError::ImageTooSmall { dimensions : img.dimensions(), minimum : (segment_size as u32, segment_size as u32) });
+    }
+
+    // Binary pattern
+    let pattern : Vec<f64> = img.to_luma().into_raw().into_iter().map(|p| if p >= 128 { 1.0 } else { 0.0 }).collect();
+    let gray_level = pattern.iter().sum::<f64>() / pattern.len() as f64;
+    let variance = gray_level * (1.0 - gray_level);
+
+    // Bartlett's method, over every segment without overlap
+    let mut planner = FftPlanner::new();
+    let mut power : Vec<f64> = vec![0.0; segment_size*segment_size];
+    let mut segments = 0;
+    for segment_y in 0..height / segment_size {
+        for segment_x in 0..width / segment_size {
+            let mut segment : Vec<f64> = Vec::with_capacity(segment_size*segment_size);
+            for y in 0..segment_size {
+                let row = (segment_y*segment_size + y)*width + segment_x*segment_size;
+                segment.extend_from_slice(&pattern[row..row + segment_size]);
+            }
+
+            // Remove the segment mean, so that its DC doesn't leak in the low frequencies
+            let segment_mean = segment.iter().sum::<f64>() / segment.len() as f64;
+            segment.iter_mut().for_each(|p| *p -= segment_mean);
+
+            for (total, p) in power.iter_mut().zip(periodogram(&mut planner, &segment, segment_size)) {
+                *total += p;
+            }
+            segments += 1;
+        }
+    }
+
+    // Annuli one frequency sample wide, up to the corners of the spectrum
+    let annuli = (segment_size as f64 / 2f64.sqrt()).ceil() as usize + 1;
+    let mut sums : Vec<f64> = vec![0.0; annuli];
+    let mut squares : Vec<f64> = vec![0.0; annuli];
+    let mut counts : Vec<usize> = vec![0; annuli];
+    let half = segment_size as isize / 2;
+    for v in 0..segment_size {
+        for u in 0..segment_size {
+            // Signed frequency indices, so that the DC is at the center
+            let fu = if u as isize > half { u as isize - segment_size as isize } else { u as isize };
+            let fv = if v as isize > half { v as isize - segment_size as isize } else { v as isize };
+            let annulus = ((fu*fu + fv*fv) as f64).sqrt().round() as usize;
+            let p = power[v + u*segment_size] / segments as f64;
+            sums[annulus] += p;
+            squares[annulus] += p*p;
+            counts[annulus] += 1;
+        }
+    }
+
+    let mut frequencies : Vec<f64> = vec![];
+    let mut rapsd : Vec<f64> = vec![];
+    let mut anisotropy : Vec<f64> = vec![];
+    for annulus in 0..annuli {
+        if counts[annulus] == 0 { continue; }
+        let mean = sums[annulus] / counts[annulus] as f64;
+        let annulus_variance = (squares[annulus] / counts[annulus] as f64 - mean*mean).max(0.0);
+        frequencies.push(annulus as f64 / segment_size as f64);
+        rapsd.push(if variance > 0.0 { mean / variance } else { mean });
+        anisotropy.push(if mean > 0.0 { annulus_variance / (mean*mean) } else { 0.0 });
+    }
+
+    let principal_frequency = if gray_level <= 0.5 { gray_level.sqrt() } else { (1.0 - gray_level).sqrt() };
+
+    Ok(Spectrum { frequencies, rapsd, anisotropy, gray_level, principal_frequency })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(anisotropy : Vec<f64>, rapsd : Vec<f64>) -> Spectrum {
+        let frequencies = (0..rapsd.len()).map(|i| i as f64).collect();
+        Spectrum { frequencies, rapsd, anisotropy, gray_level : 0.5, principal_frequency : 0.5f64.sqrt() }
+    }
+
+    #[test]
+    fn mean_anisotropy() {
+        // Annuli without power are left out
+        assert!((flat(vec![0.1, 0.1, 5.0], vec![1.0, 2.0, 0.0]).mean_anisotropy_db() - -10.0).abs() < 1e-12);
+        // Isotropic annuli lower the mean
+        assert!((flat(vec![0.0, 1.0], vec![1.0, 1.0]).mean_anisotropy_db() - 10.0 * 0.5f64.log10()).abs() < 1e-12);
+        // Nothing anisotropic, or no power at all
+        assert_eq!(flat(vec![0.0, 0.0], vec![1.0, 1.0]).mean_anisotropy_db(), f64::NEG_INFINITY);
+        assert_eq!(flat(vec![0.5, 0.5], vec![0.0, 0.0]).mean_anisotropy_db(), f64::NEG_INFINITY);
+        assert_eq!(flat(vec![], vec![]).mean_anisotropy_db(), f64::NEG_INFINITY);
+    }
+
+    // Vertical stripes only have power at horizontal frequencies
+    #[test]
+    fn periodogram_orientation() {
+        let size = 8;
+        let stripes : Vec<f64> = (0..size*size).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        let power = periodogram(&mut FftPlanner::new(), &stripes, size);
+        let (u, v) = (size / 2, 0);
+        for (i, &p) in power.iter().enumerate() {
+            if i == v + u*size { assert!(p > 1.0); } else { assert!(p < 1e-12); }
+        }
+    }
+
+    #[test]
+    fn invalid_segment_size() {
+        let img = image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(8, 8, |x, y| image::Luma([((x + y) % 2 * 255) as u8])));
+        assert!(matches!(spectrum(&img, 0), Err(QualityError::InvalidParameter { name : "segment size", .. })));
+        assert!(matches!(spectrum(&img, 16), Err(QualityError::ImageTooSmall { .. })));
+    }
+}
@@ -0,0 +1,201 @@
+use image::GenericImageView;
+use crate::error::{self, QualityError};
+use crate::filter;
+
+// Constants from Wang et al., "Image quality assessment: from error visibility to structural similarity"
+static K1 : f64 = 0.01;
+static K2 : f64 = 0.03;
+static DYNAMIC_RANGE : f64 = 255.0;
+static WINDOW_STD_DEV : f64 = 1.5;
+static WINDOW_RADIUS : usize = 5;
+
+// Scale weights from Wang et al., "Multi-scale structural similarity for image quality assessment"
+static MS_SSIM_WEIGHTS : [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+// Per-pixel SSIM values, stored as a flat 2D array
+#[derive(Debug, Clone)]
+pub struct SsimMap {
+    pub width : usize, pub height : usize,
+    pub values : Vec<f64>
+}
+
+impl SsimMap {
+    pub fn get(&self, x : usize, y : usize) -> f64 {
+        self.values[x + y*self.width]
+    }
+}
+
+// Luminance plane of an image, as floats
+fn luma(img : &image::DynamicImage) -> Vec<f64> {
+    img.to_luma().into_raw().into_iter().map(|p| p as f64).collect()
+}
+
+// 2x2 average downsampling, odd last row/column is dropped
+fn downsample(data : &[f64], width : usize, height : usize) -> (Vec<f64>, usize, usize) {
+    let new_width = width / 2;
+    let new_height = height / 2;
+    let mut result : Vec<f64> = Vec::with_capacity(new_width * new_height);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let sum = data[2*x + 2*y*width] + data[2*x + 1 + 2*y*width]
+                + data[2*x + (2*y + 1)*width] + data[2*x + 1 + (2*y + 1)*width];
+            result.push(sum / 4.0);
+        }
+    }
+    (result, new_width, new_height)
+}
+
+// Per-pixel luminance and contrast-structure terms of SSIM
+fn ssim_components(x : &[f64], y : &[f64], width : usize, height : usize) -> (Vec<f64>, Vec<f64>) {
+    let c1 = (K1 * DYNAMIC_RANGE).powi(2);
+    let c2 = (K2 * DYNAMIC_RANGE).powi(2);
+    let window = filter::gaussian_kernel(WINDOW_STD_DEV, WINDOW_RADIUS);
+
+    let xx : Vec<f64> = x.iter().map(|v| v*v).collect();
+    let yy : Vec<f64> = y.iter().map(|v| v*v).collect();
+    let xy : Vec<f64> = x.iter().zip(y.iter()).map(|(a, b)| a*b).collect();
+
+    let mu_x = filter::separable_filter(x, width, height, &window);
+    let mu_y = filter::separable_filter(y, width, height, &window);
+    let mu_xx = filter::separable_filter(&xx, width, height, &window);
+    let mu_yy = filter::separable_filter(&yy, width, height, &window);
+    let mu_xy = filter::separable_filter(&xy, width, height, &window);
+
+    let mut luminance : Vec<f64> = Vec::with_capacity(x.len());
+    let mut contrast_structure : Vec<f64> = Vec::with_capacity(x.len());
+    for i in 0..x.len() {
+        let var_x = mu_xx[i] - mu_x[i]*mu_x[i];
+        let var_y = mu_yy[i] - mu_y[i]*mu_y[i];
+        let covar = mu_xy[i] - mu_x[i]*mu_y[i];
+        luminance.push((2.0*mu_x[i]*mu_y[i] + c1) / (mu_x[i]*mu_x[i] + mu_y[i]*mu_y[i] + c1));
+        contrast_structure.push((2.0*covar + c2) / (var_x + var_y + c2));
+    }
+
+    (luminance, contrast_structure)
+}
+
+fn mean(values : &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// Compute structural similarity index, along with the per-pixel SSIM map
+pub fn ssim_map(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<(f64, SsimMap), QualityError> {
+    error::check_compatible(original_img, new_img)?;
+    let width = original_img.dimensions().0 as usize;
+    let height = original_img.dimensions().1 as usize;
+
+    let (luminance, contrast_structure) = ssim_components(&luma(original_img), &luma(new_img), width, height);
+    let values : Vec<f64> = luminance.iter().zip(contrast_structure.iter()).map(|(l, cs)| l*cs).collect();
+
+    Ok((mean(&values), SsimMap{width, height, values}))
+}
+
+// Compute structural similarity index
+pub fn ssim(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<f64, QualityError> {
+    ssim_map(original_img, new_img).map(|(ssim, _)| ssim)
+}
+
+// Compute multi-scale structural similarity index, along with a full resolution MS-SSIM map
+// Each pixel of the map combines the local terms of every scale, upsampled with nearest neighbour
+pub fn ms_ssim_map(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<(f64, SsimMap), QualityError> {
+    error::check_compatible(original_img, new_img)?;
+    let width = original_img.dimensions().0 as usize;
+    let height = original_img.dimensions().1 as usize;
+
+    // The coarsest scale must still hold a full SSIM window
+    let scales = MS_SSIM_WEIGHTS.len();
+    let min_size = (2*WINDOW_RADIUS + 1) << (scales - 1);
+    if width < min_size || height < min_size { return Err(QualityError::ImageTooSmall { dimensions : original_img.dimensions(), minimum : (min_size as u32, min_size as u32) }); }
+
+    let mut x = luma(original_img);
+    let mut y = luma(new_img);
+    let (mut scale_width, mut scale_height) = (width, height);
+    let mut score = 1.0;
+    let mut values : Vec<f64> = vec![1.0; width*height];
+
+    for (scale, &weight) in MS_SSIM_WEIGHTS.iter().enumerate() {
+        let (luminance, contrast_structure) = ssim_components(&x, &y, scale_width, scale_height);
+
+        // Contrast-structure only, except at the coarsest scale which also weights luminance
+        // As in Wang et al., the terms are averaged first, and only a negative mean is clamped before the fractional power
+        let last = scale == scales - 1;
+        let terms : Vec<f64> = if last {
+            luminance.iter().zip(contrast_structure.iter()).map(|(l, cs)| l*cs).collect()
+        } else {
+            contrast_structure
+        };
+        score *= mean(&terms).max(0.0).powf(weight);
+
+        // The map has no mean to clamp, so its terms are clamped one by one
+        for py in 0..height {
+            for px in 0..width {
+                let sx = (px >> scale).min(scale_width - 1);
+                let sy = (py >> scale).min(scale_height - 1);
+                values[px + py*width] *= terms[sx + sy*scale_width].max(0.0).powf(weight);
+            }
+        }
+
+        if !last {
+            let (new_x, new_width, new_height) = downsample(&x, scale_width, scale_height);
+            let (new_y, _, _) = downsample(&y, scale_width, scale_height);
+            x = new_x;
+            y = new_y;
+            scale_width = new_width;
+            scale_height = new_height;
+        }
+    }
+
+    Ok((score, SsimMap{width, height, values}))
+}
+
+// Compute multi-scale structural similarity index
+pub fn ms_ssim(original_img : &image::DynamicImage, new_img : &image::DynamicImage) -> Result<f64, QualityError> {
+    ms_ssim_map(original_img, new_img).map(|(ms_ssim, _)| ms_ssim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width : u32, height : u32, pixel : impl Fn(u32, u32) -> u32) -> image::DynamicImage {
+        image::DynamicImage::ImageLuma8(image::ImageBuffer::from_fn(width, height, |x, y| image::Luma([pixel(x, y).min(255) as u8])))
+    }
+
+    fn texture(x : u32, y : u32) -> u32 {
+        (x*37 + y*91 + (x*y) % 13 * 7) % 256
+    }
+
+    fn ramp(x : u32, y : u32) -> u32 {
+        (x*3 + y*2) % 200 + (x/4 + y/4) % 2 * 40
+    }
+
+    #[test]
+    fn identical_images() {
+        let img = gray(176, 180, texture);
+        assert!((ssim(&img, &img).unwrap() - 1.0).abs() < 1e-12);
+        assert!((ms_ssim(&img, &img).unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ms_ssim_too_small() {
+        let img = gray(176, 175, texture);
+        assert_eq!(ms_ssim(&img, &img), Err(QualityError::ImageTooSmall { dimensions : (176, 175), minimum : (176, 176) }));
+        let img = gray(175, 176, texture);
+        assert!(matches!(ms_ssim(&img, &img), Err(QualityError::ImageTooSmall { .. })));
+    }
+
+    // Reference values from a direct evaluation of the formulas of Wang et al. with a 2D window, renormalized at the borders
+    #[test]
+    fn reference_values() {
+        let noisy = |x, y| (texture(x, y) + (x*7 + y*3) % 11 * 4).saturating_sub(20);
+        assert!((ssim(&gray(32, 24, texture), &gray(32, 24, noisy)).unwrap() - 0.985451975114).abs() < 1e-9);
+
+        // Flat images only differ in luminance, the same at every pixel
+        let luminance = (2.0*100.0*150.0 + 6.5025) / (100.0f64.powi(2) + 150.0f64.powi(2) + 6.5025);
+        assert!((ssim(&gray(16, 16, |_, _| 100), &gray(16, 16, |_, _| 150)).unwrap() - luminance).abs() < 1e-12);
+
+        // Inverted on the right, where the contrast-structure terms are negative and must lower the mean
+        let flipped = |x, y| if x >= 132 { 255 - ramp(x, y) } else { ramp(x, y) };
+        assert!((ms_ssim(&gray(176, 176, ramp), &gray(176, 176, flipped)).unwrap() - 0.525064823470).abs() < 1e-9);
+    }
+}